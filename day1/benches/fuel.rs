@@ -0,0 +1,18 @@
+// Fuel for every module of the puzzle input, with both approaches
+
+use std::str::FromStr;
+
+use day1::fuel_sums;
+use intcode::bench;
+
+const INPUT : &str = include_str!("../src/input.txt");
+
+const ITERATIONS : usize = 1000;
+
+fn main() {
+    let modules : Vec<f32> = INPUT.lines().map(|line| f32::from_str(line).unwrap()).collect();
+
+    let measurement = bench::measure("day1/fuel", ITERATIONS, || fuel_sums(&modules))
+        .per_iteration(modules.len() as u64, "modules");
+    bench::report(&[measurement]);
+}
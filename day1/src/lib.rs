@@ -0,0 +1,21 @@
+// Fuel for a module's mass alone
+pub fn fuel_for_mass(mass: f32) -> f32 {
+    (mass / 3.0).floor() - 2.0
+}
+
+// Fuel for a module, counting the fuel needed to carry the fuel itself
+pub fn fuel_for_module(module: f32) -> f32 {
+    let fuel: f32 = fuel_for_mass(module);
+    if fuel <= 0.0 {
+        0.0
+    } else {
+        fuel + fuel_for_module(fuel)
+    }
+}
+
+// Total fuel for all modules with the first and the second approach
+pub fn fuel_sums(modules: &[f32]) -> (f32, f32) {
+    let fuel_sum: f32 = modules.iter().map(|n| fuel_for_mass(*n)).sum();
+    let fuel_sum2: f32 = modules.iter().map(|n| fuel_for_module(*n)).sum();
+    (fuel_sum, fuel_sum2)
+}
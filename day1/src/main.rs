@@ -3,6 +3,9 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use std::str::FromStr;
 
+use day1::fuel_sums;
+
+#[allow(clippy::needless_return)]
 fn read_input() -> Vec<f32> {
     let f = File::open("src/input.txt").unwrap();
     let f = BufReader::new(f);
@@ -19,18 +22,7 @@ fn main() {
     let input = read_input();
     println!("{:?}", input);
 
-    let fuelSum: f32 = input.iter().map(|n| (n / 3.0).floor() - 2.0).sum();
-    println!("Fuel sum: {} (first approach)", fuelSum);
-
-    let fuelSum2: f32 = input.iter().map(|n| calcFuelForModule(*n)).sum();
-    println!("Fuel sum {:?} (second approach)", fuelSum2);
-}
-
-fn calcFuelForModule(module: f32) -> f32 {
-    let fuel: f32 = (module / 3.0).floor() - 2.0;
-    if fuel <= 0.0 {
-        return 0.0;
-    } else {
-        return fuel + calcFuelForModule(fuel);
-    }
+    let (fuel_sum, fuel_sum2) = fuel_sums(&input);
+    println!("Fuel sum: {} (first approach)", fuel_sum);
+    println!("Fuel sum {:?} (second approach)", fuel_sum2);
 }
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// Minimal animated GIF89a writer, using a global palette of at most 256 colors
+pub struct Encoder<W : Write> {
+    out : W,
+    width : usize,
+    height : usize,
+    code_size : u8,
+}
+
+impl<W : Write> Encoder<W> {
+    pub fn new(mut out : W, width : usize, height : usize, palette : &[[u8; 3]]) -> io::Result<Encoder<W>> {
+        // The palette size must be a power of two, and at least 4 colors
+        let mut bits = 2;
+        while (1 << bits) < palette.len() {
+            bits += 1;
+        }
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(width as u16).to_le_bytes())?;
+        out.write_all(&(height as u16).to_le_bytes())?;
+        out.write_all(&[0x80 | ((bits - 1) << 4) | (bits - 1), 0, 0])?;
+
+        for i in 0..(1 << bits) {
+            out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+
+        // Netscape extension, loop forever
+        out.write_all(&[0x21, 0xFF, 0x0B])?;
+        out.write_all(b"NETSCAPE2.0")?;
+        out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Encoder { out, width, height, code_size : bits })
+    }
+
+    // Pixels are palette indexes, row by row; delay is in hundredths of a second
+    pub fn write_frame(&mut self, pixels : &[u8], delay : u16) -> io::Result<()> {
+        assert_eq!(pixels.len(), self.width * self.height);
+
+        // Graphic control extension
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor, covering the whole screen
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(self.width as u16).to_le_bytes())?;
+        self.out.write_all(&(self.height as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, self.code_size])?;
+
+        let data = compress(pixels, self.code_size);
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()
+    }
+}
+
+struct BitWriter {
+    bytes : Vec<u8>,
+    buffer : u32,
+    bits : u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code : u16, size : u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// Variable length LZW, as described in the GIF89a specification
+fn compress(pixels : &[u8], min_code_size : u8) -> Vec<u8> {
+    const MAX_CODE : u16 = 4096;
+
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut writer = BitWriter { bytes : Vec::new(), buffer : 0, bits : 0 };
+    let mut table : HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = min_code_size as u32 + 1;
+    let mut next = end + 1;
+
+    writer.write(clear, size);
+
+    let mut prefix = match pixels.first() {
+        Some(&pixel) => pixel as u16,
+        None => {
+            writer.write(end, size);
+            return writer.finish();
+        }
+    };
+
+    for &pixel in &pixels[1..] {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, size);
+        // The decoder grows its codes one step behind us
+        if next > (1 << size) - 1 && size < 12 {
+            size += 1;
+        }
+
+        if next < MAX_CODE {
+            table.insert((prefix, pixel), next);
+            next += 1;
+        } else {
+            writer.write(clear, size);
+            table.clear();
+            size = min_code_size as u32 + 1;
+            next = end + 1;
+        }
+
+        prefix = pixel as u16;
+    }
+
+    writer.write(prefix, size);
+    if next > (1 << size) - 1 && size < 12 {
+        size += 1;
+    }
+    writer.write(end, size);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_a_tiny_gif() {
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&mut out, 2, 2, &[[0, 0, 0], [255, 255, 255]]).unwrap();
+        encoder.write_frame(&[0, 1, 1, 0], 10).unwrap();
+        encoder.finish().unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"GIF89a\x02\x00\x02\x00\x91\x00\x00");
+        // The palette is padded to the four colors of the smallest code size
+        expected.extend_from_slice(&[0, 0, 0, 255, 255, 255, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        expected.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00, 10, 0, 0x00, 0x00]);
+        expected.extend_from_slice(&[0x2C, 0, 0, 0, 0, 2, 0, 2, 0, 0x00, 2]);
+        // Clear, 0, 1, 1 in three bits, then 0 and end in four once the
+        // table reaches code 8
+        expected.extend_from_slice(&[3, 0x44, 0x02, 0x05, 0x00]);
+        expected.push(0x3B);
+
+        assert_eq!(out, expected);
+    }
+}
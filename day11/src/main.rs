@@ -1,10 +1,17 @@
-use std::io::{self, Read};
-use std::fs::File;
 use std::collections::HashMap;
-use std::f64;
+use std::env;
+use std::process;
+use std::time::Duration;
 
-type Num = i64;
-type Pos = (Num, Num);
+mod gif;
+mod replay;
+
+use replay::{Event, Recording};
+
+use intcode::loader::{self, Source};
+
+pub type Num = i64;
+pub type Pos = (Num, Num);
 
 #[derive(Debug)]
 struct Instruction {
@@ -14,8 +21,8 @@ struct Instruction {
     mode3 : Num,
 }
 
-#[derive(Debug)]
-enum Direction {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
     N,
     S,
     E,
@@ -39,7 +46,7 @@ fn decode (i : Num) -> Instruction {
     
     let mode3 = i % 10;
     
-    return Instruction {
+    Instruction {
         opcode,
         mode1,
         mode2, 
@@ -179,69 +186,102 @@ fn change_relative_base(memory : &mut Vec<Num>,
                         instruction : Instruction,
                         relative_base : &mut Num) -> Option<usize> {
     let op1 = get_value(memory, pc+1, instruction.mode1, *relative_base);
-    *relative_base = (*relative_base) + op1; 
+    *relative_base += op1; 
     Some(pc+2)                        
 }
 
 fn read_input(filename : &str) -> Vec<Num> {
-    let mut contents = String::new();
-
-    File::open(filename)
-        .unwrap()
-        .read_to_string(&mut contents)
-        .unwrap();
-
-    contents.split(',')
-            .map(|n| Num::from_str_radix(&n.trim(), 10)
-                     .unwrap()
-                )
-            .collect()    
+    match loader::load(&Source::from_arg(filename)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", filename, e);
+            process::exit(1);
+        }
+    }
 }
 
-fn main() {
-    let mut memory = read_input("input.txt");
+struct Options {
+    mode : Mode,
+    fps : u32,
+    heatmap : bool,
+}
 
-    println!("Welcome to the INTCODE computer!");
-    
+enum Mode {
+    Count,
+    Play,
+    Frames(String),
+    Gif(String),
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options { mode : Mode::Count, fps : 30, heatmap : false };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--play" => options.mode = Mode::Play,
+            "--frames" => {
+                let dir = args.next().ok_or("--frames needs a directory")?;
+                options.mode = Mode::Frames(dir);
+            },
+            "--gif" => {
+                let file = args.next().ok_or("--gif needs a file name")?;
+                options.mode = Mode::Gif(file);
+            },
+            "--fps" => {
+                let fps = args.next().ok_or("--fps needs a value")?;
+                options.fps = match fps.parse() {
+                    Ok(fps) if fps > 0 => fps,
+                    _ => return Err(format!("Invalid frame rate {:?}", fps)),
+                };
+            },
+            "--heatmap" => options.heatmap = true,
+            _ => return Err(format!("Unknown argument {:?}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn run_robot(memory : &mut Vec<Num>) -> (HashMap<Pos, Num>, Recording) {
     let mut pc = 0;
     let mut relative_base = 0;
     let mut curr_input;
     let mut curr_output = None;
-    let mut prev_output;
 
     let mut position = (0, 0);
     let mut direction = Direction::N;
 
     let mut panels : HashMap<Pos, Num> = HashMap::new();
+    let mut recording = Recording::new();
     
     while pc < memory.len() {
         let instruction = decode(memory[pc]);
         let new_pc = match instruction.opcode {
             99 => None,
-            1 | 2 => fetch_operands_and_store_result(&mut memory, 
+            1 | 2 => fetch_operands_and_store_result(memory, 
                 pc, instruction, relative_base),
             3 => {
                 curr_input = match panels.get(&position) {
                     Some(color) => *color,
                     None => 0,
                 };
-                input(&mut memory, pc, instruction, relative_base, curr_input)
+                input(memory, pc, instruction, relative_base, curr_input)
             },
             4 => {
-                prev_output = curr_output;
+                let prev_output = curr_output;
 
                 let mut out = 0; 
-                let new_pc = output(&mut memory, pc, instruction, relative_base, &mut out);
+                let new_pc = output(memory, pc, instruction, relative_base, &mut out);
                 curr_output = Some(out);
 
                 // Outputted two values, we can paint and change
-                if prev_output.is_some() && curr_output.is_some() {
-                    // println!("Output is {:?} {:?}", prev_output, curr_output);
+                if let Some(color) = prev_output {
                     // First value is the panel color
-                    panels.insert(position, prev_output.unwrap());
-                    // println!("Panels are {:?}", panels);
+                    panels.insert(position, color);
+                    recording.push(Event::Paint { position, color });
 
-                    let change = curr_output.unwrap();
+                    let change = out;
                     // Change direction
                     direction = match direction {
                         Direction::N => if change == 0 { Direction::W } else { Direction::E },
@@ -249,6 +289,7 @@ fn main() {
                         Direction::W => if change == 0 { Direction::S } else { Direction::N },
                         Direction::E => if change == 0 { Direction::N } else { Direction::S }
                     };
+                    recording.push(Event::Turn { direction });
                     
                     // Move by the current direction
                     let delta = match direction {
@@ -258,29 +299,59 @@ fn main() {
                         Direction::W => (-1, 0)
                     };
                     position = (position.0 + delta.0, position.1 + delta.1);
-                    
+                    recording.push(Event::Move { position });
 
-                    // println!("New position and direction are {:?} {:?}", position, direction);
                     // Clean the outputs
                     curr_output = None;
                 }
 
                 new_pc
             },
-            5 | 6 => jump_if(&mut memory, pc, instruction, relative_base),
-            7 | 8 => comparison(&mut memory, pc, instruction, 
+            5 | 6 => jump_if(memory, pc, instruction, relative_base),
+            7 | 8 => comparison(memory, pc, instruction, 
                 relative_base),
-            9 => change_relative_base(&mut memory, pc, instruction, 
+            9 => change_relative_base(memory, pc, instruction, 
                 &mut relative_base),
             _ => panic!("Unexpected opcode in instruction {:?}", instruction),
         };
         match new_pc {
             Some(new_pc) => {pc = new_pc;}
-            None => {
-                println!("Halt!");
-                println!("Number of unique panels {:?}", panels.len()); 
-                return;
-            }
+            None => break,
+        }
+    }
+
+    (panels, recording)
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: day11 [--play | --frames DIR | --gif FILE] [--fps N] [--heatmap]");
+            process::exit(1);
         }
+    };
+
+    let mut memory = read_input("input.txt");
+
+    println!("Welcome to the INTCODE computer!");
+
+    let (panels, recording) = run_robot(&mut memory);
+
+    println!("Halt!");
+    println!("Number of unique panels {:?}", panels.len());
+
+    let frame_time = Duration::from_secs(1) / options.fps;
+    let result = match options.mode {
+        Mode::Count => Ok(()),
+        Mode::Play => recording.play(frame_time, options.heatmap),
+        Mode::Frames(dir) => recording.export_frames(&dir, options.heatmap),
+        Mode::Gif(file) => recording.export_gif(&file, frame_time, options.heatmap),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Could not write the replay: {}", e);
+        process::exit(1);
     }
 }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Direction, Num, Pos};
+use crate::gif::Encoder;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Paint { position : Pos, color : Num },
+    Turn { direction : Direction },
+    Move { position : Pos },
+}
+
+pub struct Recording {
+    events : Vec<Event>,
+}
+
+// The hull as it looks after a given number of robot steps
+struct Frame {
+    colors : Vec<Num>,
+    paints : Vec<u32>,
+    width : usize,
+    robot : usize,
+    direction : Direction,
+    max_paints : u32,
+}
+
+// Characters used for the heatmap, from never painted to most painted
+const HEAT_CHARS : &[u8] = b" .:-=+*#%@";
+// Grayscale ramp of the 256 color palette, used by the terminal heatmap
+const HEAT_ANSI : [u8; 10] = [232, 235, 238, 241, 244, 247, 250, 253, 226, 196];
+
+impl Recording {
+    pub fn new() -> Recording {
+        Recording { events : Vec::new() }
+    }
+
+    pub fn push(&mut self, event : Event) {
+        self.events.push(event);
+    }
+
+    // Smallest rectangle containing every position the robot visited
+    fn bounds(&self) -> (Pos, Pos) {
+        let mut min = (0, 0);
+        let mut max = (0, 0);
+
+        for event in &self.events {
+            let position = match event {
+                Event::Paint { position, .. } | Event::Move { position } => *position,
+                Event::Turn { .. } => continue,
+            };
+            min = (min.0.min(position.0), min.1.min(position.1));
+            max = (max.0.max(position.0), max.1.max(position.1));
+        }
+
+        (min, max)
+    }
+
+    // Replays the events, producing a frame every time the robot moves
+    fn for_each_frame<F>(&self, mut f : F) -> io::Result<()>
+        where F : FnMut(usize, &Frame) -> io::Result<()> {
+        let (min, max) = self.bounds();
+        let width = (max.0 - min.0 + 1) as usize;
+        let height = (max.1 - min.1 + 1) as usize;
+
+        // The top row of the frame is the northernmost one
+        let index = |position : Pos| {
+            ((max.1 - position.1) as usize) * width + (position.0 - min.0) as usize
+        };
+
+        let max_paints = self.events.iter().fold(HashMap::new(), |mut counts, event| {
+            if let Event::Paint { position, .. } = event {
+                *counts.entry(*position).or_insert(0) += 1;
+            }
+            counts
+        }).values().copied().max().unwrap_or(0);
+
+        let mut frame = Frame {
+            colors : vec![0; width * height],
+            paints : vec![0; width * height],
+            width,
+            robot : index((0, 0)),
+            direction : Direction::N,
+            max_paints,
+        };
+        let mut step = 0;
+
+        f(step, &frame)?;
+
+        for event in &self.events {
+            match *event {
+                Event::Paint { position, color } => {
+                    frame.colors[index(position)] = color;
+                    frame.paints[index(position)] += 1;
+                },
+                Event::Turn { direction } => frame.direction = direction,
+                Event::Move { position } => {
+                    frame.robot = index(position);
+                    step += 1;
+                    f(step, &frame)?;
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn steps(&self) -> usize {
+        self.events.iter().filter(|event| matches!(event, Event::Move { .. })).count()
+    }
+
+    pub fn play(&self, frame_time : Duration, heatmap : bool) -> io::Result<()> {
+        let steps = self.steps();
+        let stdout = io::stdout();
+
+        self.for_each_frame(|step, frame| {
+            let mut out = stdout.lock();
+            write!(out, "\x1B[2J\x1B[H{}", frame.to_ansi(heatmap))?;
+            writeln!(out, "Step {}/{}", step, steps)?;
+            out.flush()?;
+            thread::sleep(frame_time);
+            Ok(())
+        })
+    }
+
+    pub fn export_frames(&self, dir : &str, heatmap : bool) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        self.for_each_frame(|step, frame| {
+            let path = Path::new(dir).join(format!("frame{:05}.txt", step));
+            fs::write(path, frame.to_text(heatmap))
+        })
+    }
+
+    pub fn export_gif(&self, filename : &str, frame_time : Duration, heatmap : bool) -> io::Result<()> {
+        const SCALE : usize = 4;
+
+        let (min, max) = self.bounds();
+        let width = (max.0 - min.0 + 1) as usize;
+        let height = (max.1 - min.1 + 1) as usize;
+
+        let mut palette = vec![[0, 0, 0], [255, 255, 255], [255, 0, 0]];
+        // Heatmap colors go from dark blue to yellow
+        for i in 0..HEAT_CHARS.len() {
+            let t = (i * 255 / (HEAT_CHARS.len() - 1)) as u8;
+            palette.push([t, t / 2, 255 - t]);
+        }
+
+        let file = BufWriter::new(File::create(filename)?);
+        let mut encoder = Encoder::new(file, width * SCALE, height * SCALE, &palette)?;
+        let delay = (frame_time.as_millis() / 10).max(1) as u16;
+        let mut pixels = Vec::with_capacity(width * height * SCALE * SCALE);
+
+        self.for_each_frame(|_, frame| {
+            pixels.clear();
+            for row in 0..height {
+                let line : Vec<u8> = (row * width..(row + 1) * width).flat_map(|i| {
+                    let index = if i == frame.robot {
+                        2
+                    } else if heatmap {
+                        3 + frame.heat(frame.paints[i]) as u8
+                    } else if frame.colors[i] == 1 {
+                        1
+                    } else {
+                        0
+                    };
+                    vec![index; SCALE]
+                }).collect();
+                for _ in 0..SCALE {
+                    pixels.extend_from_slice(&line);
+                }
+            }
+            encoder.write_frame(&pixels, delay)
+        })?;
+
+        encoder.finish()
+    }
+}
+
+impl Frame {
+    // Position of the paint count in the heatmap ramp
+    fn heat(&self, paints : u32) -> usize {
+        if paints == 0 || self.max_paints == 0 {
+            0
+        } else {
+            1 + (paints - 1) as usize * (HEAT_CHARS.len() - 2) / (self.max_paints - 1).max(1) as usize
+        }
+    }
+
+    fn robot_glyph(&self) -> char {
+        match self.direction {
+            Direction::N => '^',
+            Direction::S => 'v',
+            Direction::E => '>',
+            Direction::W => '<',
+        }
+    }
+
+    fn to_text(&self, heatmap : bool) -> String {
+        let mut text = String::new();
+        for row in 0..self.colors.len() / self.width {
+            for i in row * self.width..(row + 1) * self.width {
+                text.push(if i == self.robot {
+                    self.robot_glyph()
+                } else if heatmap {
+                    HEAT_CHARS[self.heat(self.paints[i])] as char
+                } else if self.colors[i] == 1 {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn to_ansi(&self, heatmap : bool) -> String {
+        let mut text = String::new();
+        for row in 0..self.colors.len() / self.width {
+            for i in row * self.width..(row + 1) * self.width {
+                let background = if heatmap {
+                    HEAT_ANSI[self.heat(self.paints[i])]
+                } else if self.colors[i] == 1 {
+                    15
+                } else {
+                    0
+                };
+                let glyph = if i == self.robot { self.robot_glyph() } else { ' ' };
+                text.push_str(&format!("\x1B[48;5;{}m\x1B[38;5;9m{}", background, glyph));
+            }
+            text.push_str("\x1B[0m\n");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_follow_the_robot() {
+        let mut recording = Recording::new();
+        recording.push(Event::Paint { position : (0, 0), color : 1 });
+        recording.push(Event::Turn { direction : Direction::E });
+        recording.push(Event::Move { position : (1, 0) });
+        recording.push(Event::Paint { position : (1, 0), color : 0 });
+        recording.push(Event::Turn { direction : Direction::S });
+        recording.push(Event::Move { position : (1, -1) });
+
+        let mut frames = Vec::new();
+        recording.for_each_frame(|step, frame| {
+            frames.push((step, frame.to_text(false)));
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(frames, [
+            (0, String::from("^.\n..\n")),
+            (1, String::from("#>\n..\n")),
+            (2, String::from("#.\n.v\n")),
+        ]);
+        assert_eq!(recording.steps(), 2);
+    }
+
+    #[test]
+    fn heatmap_ramps_up_to_the_most_painted_panel() {
+        let mut recording = Recording::new();
+        for color in [1, 0, 1] {
+            recording.push(Event::Paint { position : (0, 0), color });
+        }
+        recording.push(Event::Paint { position : (1, 0), color : 1 });
+        recording.push(Event::Move { position : (0, -1) });
+
+        let mut last = String::new();
+        recording.for_each_frame(|_, frame| {
+            last = frame.to_text(true);
+            Ok(())
+        }).unwrap();
+
+        // Painted three times is the hottest, once the coolest painted
+        assert_eq!(last, "@.\n^ \n");
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+use intcode::diff::Snapshot as Dump;
+
+use crate::{Instruction, Num, decode, fetch_operands_and_store_result, input, output, jump_if, comparison,
+            change_relative_base};
+use crate::controller::{Action, Controller, Joystick};
+use crate::render::Renderer;
+use crate::screen::{Screen, ScreenError};
+
+// The arcade machine: the intcode computer and the screen it draws on
+pub struct Cabinet {
+    memory : Vec<Num>,
+    pc : usize,
+    relative_base : Num,
+    out_buffer : Vec<Num>,
+    screen : Screen,
+    // Every joystick move consumed so far, enough to replay the session
+    inputs : Vec<Joystick>,
+    rewind : Option<Rewind>,
+    dump : Option<Dumps>,
+}
+
+// Machine state saved while the program waits for input
+struct Snapshot {
+    memory : Vec<Num>,
+    pc : usize,
+    relative_base : Num,
+    screen : Screen,
+    inputs : usize,
+    // The dump's writers, so they match the memory again after rewinding
+    writers : Option<BTreeMap<usize, usize>>,
+}
+
+// Where every frame's machine state goes, for diffing with intcode-diff
+struct Dumps {
+    directory : PathBuf,
+    // The pc of the instruction that last wrote each address
+    writers : BTreeMap<usize, usize>,
+    // Frames are numbered by how many were dumped, not by the moves left
+    // after rewinding, so later frames don't overwrite earlier ones
+    frames : usize,
+}
+
+struct Rewind {
+    interval : usize,
+    capacity : usize,
+    snapshots : VecDeque<Snapshot>,
+}
+
+impl Cabinet {
+    pub fn new(memory : Vec<Num>) -> Cabinet {
+        Cabinet {
+            memory,
+            pc : 0,
+            relative_base : 0,
+            out_buffer : Vec::with_capacity(3),
+            screen : Screen::new(),
+            inputs : Vec::new(),
+            rewind : None,
+            dump : None,
+        }
+    }
+
+    // Writes the machine state into the directory every time the program
+    // asks for input, as frame-00042.txt and so on
+    pub fn enable_dump(&mut self, directory : &str) {
+        self.dump = Some(Dumps { directory : PathBuf::from(directory), writers : BTreeMap::new(), frames : 0 });
+    }
+
+    // Takes a snapshot every `interval` frames, keeping the last `capacity` of
+    // them. Rewinding goes back at least `interval` frames.
+    pub fn enable_rewind(&mut self, interval : usize, capacity : usize) {
+        self.rewind = Some(Rewind {
+            interval : interval.max(1),
+            capacity : capacity.max(1),
+            snapshots : VecDeque::new(),
+        });
+    }
+
+    pub fn inputs(&self) -> &[Joystick] {
+        &self.inputs
+    }
+
+    fn take_snapshot(&mut self) {
+        let frame = self.inputs.len();
+        let rewind = match self.rewind.as_mut() {
+            Some(rewind) if frame.is_multiple_of(rewind.interval) => rewind,
+            _ => return,
+        };
+
+        if rewind.snapshots.back().map(|s| s.inputs) == Some(frame) {
+            return;
+        }
+        if rewind.snapshots.len() == rewind.capacity {
+            rewind.snapshots.pop_front();
+        }
+        rewind.snapshots.push_back(Snapshot {
+            memory : self.memory.clone(),
+            pc : self.pc,
+            relative_base : self.relative_base,
+            screen : self.screen.clone(),
+            inputs : frame,
+            writers : self.dump.as_ref().map(|dump| dump.writers.clone()),
+        });
+    }
+
+    // Remembers which address the instruction at pc is about to write
+    fn track_write(&mut self, pc : usize, instruction : &Instruction) {
+        let dump = match self.dump.as_mut() {
+            Some(dump) => dump,
+            None => return,
+        };
+        let (position, mode) = match instruction.opcode {
+            1 | 2 | 7 | 8 => (pc + 3, instruction.mode3),
+            3 => (pc + 1, instruction.mode1),
+            _ => return,
+        };
+        let value = self.memory.get(position).copied().unwrap_or(0);
+        let address = if mode == 2 { value + self.relative_base } else { value };
+        if address >= 0 {
+            dump.writers.insert(address as usize, pc);
+        }
+    }
+
+    fn dump_frame(&mut self) {
+        let dump = match self.dump.as_mut() {
+            Some(dump) => dump,
+            None => return,
+        };
+
+        let snapshot = Dump {
+            memory : self.memory.clone(),
+            pc : self.pc,
+            relative_base : self.relative_base,
+            writers : dump.writers.clone(),
+        };
+        let path = dump.directory.join(format!("frame-{:05}.txt", dump.frames));
+        dump.frames += 1;
+        if let Err(e) = fs::write(&path, snapshot.save()) {
+            eprintln!("Could not write {}, not dumping any more frames: {}", path.display(), e);
+            self.dump = None;
+        }
+    }
+
+    // Restores the newest snapshot that is at least one interval old, and
+    // forgets the moves made after it so the recording stays replayable
+    fn restore_snapshot(&mut self) {
+        let frame = self.inputs.len();
+        let rewind = match self.rewind.as_mut() {
+            Some(rewind) => rewind,
+            None => return,
+        };
+
+        while let Some(snapshot) = rewind.snapshots.pop_back() {
+            if snapshot.inputs + rewind.interval <= frame || rewind.snapshots.is_empty() {
+                self.memory = snapshot.memory;
+                self.pc = snapshot.pc;
+                self.relative_base = snapshot.relative_base;
+                self.screen = snapshot.screen;
+                self.out_buffer.clear();
+                self.inputs.truncate(snapshot.inputs);
+                if let (Some(dump), Some(writers)) = (self.dump.as_mut(), snapshot.writers) {
+                    dump.writers = writers;
+                }
+                return;
+            }
+        }
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    // Runs until the program halts or the controller stops, returning the score.
+    // Every time the program asks for input a new frame is drawn.
+    pub fn run(&mut self,
+               controller : &mut dyn Controller,
+               mut renderer : Option<&mut Renderer>) -> Result<Num, ScreenError> {
+        while self.pc < self.memory.len() {
+            let pc = self.pc;
+            let instruction = decode(self.memory[pc]);
+            if self.dump.is_some() && instruction.opcode != 3 {
+                self.track_write(pc, &instruction);
+            }
+            let memory = &mut self.memory;
+            let relative_base = &mut self.relative_base;
+            let new_pc = match instruction.opcode {
+                99 => None,
+                1 | 2 => fetch_operands_and_store_result(memory,
+                    pc, instruction, *relative_base),
+                3 => {
+                    self.take_snapshot();
+                    self.dump_frame();
+                    if let Some(renderer) = renderer.as_mut() {
+                        renderer.draw(&self.screen);
+                    }
+                    match controller.next_action(&self.screen) {
+                        Action::Move(joystick) => {
+                            self.track_write(pc, &instruction);
+                            self.inputs.push(joystick);
+                            input(&mut self.memory, pc, instruction,
+                                self.relative_base, joystick.value())
+                        },
+                        Action::Rewind => {
+                            self.restore_snapshot();
+                            if let Some(renderer) = renderer.as_mut() {
+                                renderer.invalidate();
+                            }
+                            continue;
+                        },
+                        Action::Stop => return Ok(self.screen.score()),
+                    }
+                },
+                4 => {
+                    let mut out = 0;
+                    let new_pc = output(memory, pc, instruction, *relative_base, &mut out);
+
+                    self.out_buffer.push(out);
+
+                    if self.out_buffer.len() == 3 {
+                        self.screen.update(self.out_buffer[0], self.out_buffer[1], self.out_buffer[2])?;
+                        self.out_buffer.clear();
+                    }
+
+                    new_pc
+                },
+                5 | 6 => jump_if(memory, pc, instruction, *relative_base),
+                7 | 8 => comparison(memory, pc, instruction,
+                    *relative_base),
+                9 => change_relative_base(memory, pc, instruction,
+                    relative_base),
+                _ => panic!("Unexpected opcode in instruction {:?}", instruction),
+            };
+
+            match new_pc {
+                Some(new_pc) => {self.pc = new_pc;}
+                None => break,
+            }
+        }
+
+        if let Some(renderer) = renderer {
+            renderer.draw(&self.screen);
+        }
+
+        Ok(self.screen.score())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Scripted;
+    use crate::screen::Tile;
+
+    // Moves a paddle along the top row by each input, erasing it where it
+    // was, and scores one point per frame
+    fn paddle_program() -> Vec<Num> {
+        let mut memory = vec![
+            3, 100,
+            4, 101, 104, 0, 104, 0,
+            1, 100, 101, 101,
+            4, 101, 104, 0, 104, 3,
+            1001, 102, 1, 102,
+            104, -1, 104, 0, 4, 102,
+            1105, 1, 0,
+        ];
+        memory.resize(103, 0);
+        memory[101] = 5;
+        memory
+    }
+
+    // Plays the actions in order, then stops
+    struct Actions(Vec<Action>);
+
+    impl Controller for Actions {
+        fn next_action(&mut self, _screen : &Screen) -> Action {
+            if self.0.is_empty() { Action::Stop } else { self.0.remove(0) }
+        }
+    }
+
+    fn play(moves : &[Joystick]) -> Cabinet {
+        let mut cabinet = Cabinet::new(paddle_program());
+        cabinet.run(&mut Scripted::new(moves.to_vec()), None).unwrap();
+        cabinet
+    }
+
+    const MOVES : [Joystick; 5] = [Joystick::Right, Joystick::Right, Joystick::Left, Joystick::Neutral, Joystick::Right];
+
+    #[test]
+    fn replaying_the_recording_reproduces_the_session() {
+        let mut cabinet = Cabinet::new(paddle_program());
+        cabinet.enable_rewind(2, 4);
+        let mut actions = Actions(MOVES.iter().map(|&joystick| Action::Move(joystick)).collect());
+        actions.0.insert(3, Action::Rewind);
+        assert_eq!(cabinet.run(&mut actions, None).unwrap(), 2);
+        // The snapshot at frame 2 is too recent, so it goes back to frame 0
+        assert_eq!(cabinet.inputs(), [Joystick::Neutral, Joystick::Right]);
+
+        let replay = play(cabinet.inputs());
+        assert_eq!(replay.inputs(), cabinet.inputs());
+        assert_eq!(replay.screen(), cabinet.screen());
+        assert_eq!(replay.screen().paddle(), Some((6, 0)));
+    }
+
+    #[test]
+    fn rewinding_goes_back_at_least_one_interval() {
+        let mut cabinet = Cabinet::new(paddle_program());
+        cabinet.enable_rewind(2, 4);
+        let mut actions = Actions(MOVES.iter().map(|&joystick| Action::Move(joystick)).collect());
+        actions.0.push(Action::Rewind);
+        cabinet.run(&mut actions, None).unwrap();
+
+        // Snapshots were taken at frames 0, 2 and 4, and 4 is too recent
+        // after 5 moves
+        assert_eq!(cabinet.inputs(), &MOVES[..2]);
+        assert_eq!(cabinet.screen(), play(&MOVES[..2]).screen());
+        assert_eq!(cabinet.screen().score(), 2);
+        assert_eq!(cabinet.screen().get(5, 0), Tile::Empty);
+    }
+
+    #[test]
+    fn rewinding_stops_at_the_oldest_snapshot_kept() {
+        let mut cabinet = Cabinet::new(paddle_program());
+        // Only the snapshot of frame 4 is left once the others are evicted
+        cabinet.enable_rewind(2, 1);
+        let mut actions = Actions(MOVES.iter().map(|&joystick| Action::Move(joystick)).collect());
+        actions.0.push(Action::Rewind);
+        cabinet.run(&mut actions, None).unwrap();
+
+        assert_eq!(cabinet.inputs(), &MOVES[..4]);
+        assert_eq!(cabinet.screen(), play(&MOVES[..4]).screen());
+    }
+
+    #[test]
+    fn rewinding_without_snapshots_does_nothing() {
+        let mut cabinet = Cabinet::new(paddle_program());
+        let mut actions = Actions(vec![Action::Move(Joystick::Left), Action::Rewind, Action::Move(Joystick::Left)]);
+        cabinet.run(&mut actions, None).unwrap();
+
+        assert_eq!(cabinet.inputs(), [Joystick::Left, Joystick::Left]);
+        assert_eq!(cabinet.screen().paddle(), Some((3, 0)));
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+use crate::{Num, Pos};
+use crate::screen::{Screen, Tile};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Joystick {
+    Left,
+    Neutral,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Move(Joystick),
+    // Go back to a snapshot taken a few seconds ago
+    Rewind,
+    // Give up and end the game with the current score
+    Stop,
+}
+
+// Decides the joystick position every time the cabinet asks for input
+pub trait Controller {
+    fn next_action(&mut self, screen : &Screen) -> Action;
+}
+
+impl Joystick {
+    pub fn value(self) -> Num {
+        match self {
+            Joystick::Left => -1,
+            Joystick::Neutral => 0,
+            Joystick::Right => 1,
+        }
+    }
+
+    pub fn from_value(value : Num) -> Option<Joystick> {
+        match value {
+            -1 => Some(Joystick::Left),
+            0 => Some(Joystick::Neutral),
+            1 => Some(Joystick::Right),
+            _ => None,
+        }
+    }
+
+    // Moves the paddle towards the given column
+    fn towards(paddle : Num, target : Num) -> Joystick {
+        if target < paddle {
+            Joystick::Left
+        } else if target > paddle {
+            Joystick::Right
+        } else {
+            Joystick::Neutral
+        }
+    }
+}
+
+// Asks for every move on stdin
+pub struct Human;
+
+impl Controller for Human {
+    fn next_action(&mut self, _screen : &Screen) -> Action {
+        loop {
+            let mut buffer = String::new();
+
+            println!("Please input a value");
+            match io::stdin().read_line(&mut buffer) {
+                Ok(0) | Err(_) => return Action::Stop,
+                Ok(_) => (),
+            }
+
+            match buffer.trim().parse().ok().and_then(Joystick::from_value) {
+                Some(joystick) => return Action::Move(joystick),
+                None => println!("Expected -1, 0 or 1"),
+            }
+        }
+    }
+}
+
+// Keeps the paddle under the ball
+pub struct Autopilot;
+
+impl Controller for Autopilot {
+    fn next_action(&mut self, screen : &Screen) -> Action {
+        match (screen.ball(), screen.paddle()) {
+            (Some(ball), Some(paddle)) => Action::Move(Joystick::towards(paddle.0, ball.0)),
+            _ => Action::Move(Joystick::Neutral),
+        }
+    }
+}
+
+// Moves the paddle to where the ball is going to land
+pub struct Predictive {
+    last_ball : Option<Pos>,
+}
+
+impl Predictive {
+    pub fn new() -> Predictive {
+        Predictive { last_ball : None }
+    }
+
+    // Simulates the ball bouncing off walls and blocks until it comes back
+    // down to the paddle row, returning the column where it gets there
+    fn landing(screen : &Screen, ball : Pos, velocity : Pos, paddle_row : Num) -> Option<Num> {
+        // Enough to cross the screen a few times
+        let limit = 4 * screen.width() * screen.height();
+
+        let mut broken = HashSet::new();
+        let mut solid = |x : Num, y : Num| {
+            match screen.get(x, y) {
+                Tile::Wall => true,
+                Tile::Block => broken.insert((x, y)),
+                _ => false,
+            }
+        };
+
+        let (mut x, mut y) = ball;
+        let (mut dx, mut dy) = velocity;
+
+        for _ in 0..limit {
+            if dy > 0 && y == paddle_row - 1 {
+                return Some(x);
+            }
+
+            // Sides first, then the corner the ball is now heading to
+            if solid(x + dx, y) {
+                dx = -dx;
+            }
+            if solid(x, y + dy) {
+                dy = -dy;
+            }
+            if solid(x + dx, y + dy) {
+                dx = -dx;
+                dy = -dy;
+            }
+
+            x += dx;
+            y += dy;
+        }
+
+        None
+    }
+}
+
+impl Controller for Predictive {
+    fn next_action(&mut self, screen : &Screen) -> Action {
+        let (ball, paddle) = match (screen.ball(), screen.paddle()) {
+            (Some(ball), Some(paddle)) => (ball, paddle),
+            _ => return Action::Move(Joystick::Neutral),
+        };
+
+        let velocity = self.last_ball.map(|last| (ball.0 - last.0, ball.1 - last.1));
+        self.last_ball = Some(ball);
+
+        let target = match velocity {
+            Some((dx, dy)) if dx.abs() == 1 && dy.abs() == 1 => {
+                Predictive::landing(screen, ball, (dx, dy), paddle.1).unwrap_or(ball.0)
+            },
+            _ => ball.0,
+        };
+
+        Action::Move(Joystick::towards(paddle.0, target))
+    }
+}
+
+// Replays moves read from a file, stopping when they run out
+pub struct Scripted {
+    moves : Vec<Joystick>,
+    next : usize,
+}
+
+impl Scripted {
+    pub fn new(moves : Vec<Joystick>) -> Scripted {
+        Scripted { moves, next : 0 }
+    }
+
+    pub fn from_file(filename : &str) -> Result<Scripted, String> {
+        let contents = fs::read_to_string(filename)
+            .map_err(|e| format!("Could not read {}: {}", filename, e))?;
+
+        let moves = contents.split(|c : char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse().ok()
+                .and_then(Joystick::from_value)
+                .ok_or_else(|| format!("Invalid move {:?} in {}", token, filename)))
+            .collect::<Result<Vec<Joystick>, String>>()?;
+
+        Ok(Scripted::new(moves))
+    }
+}
+
+impl Controller for Scripted {
+    fn next_action(&mut self, _screen : &Screen) -> Action {
+        match self.moves.get(self.next) {
+            Some(&joystick) => {
+                self.next += 1;
+                Action::Move(joystick)
+            },
+            None => Action::Stop,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    // A box 10 wide with walls on top and both sides, and the paddle on the
+    // bottom row
+    fn arena(paddle : Num) -> Screen {
+        let mut screen = Screen::new();
+        for x in 0..10 {
+            screen.update(x, 0, 1).unwrap();
+        }
+        for y in 1..8 {
+            screen.update(0, y, 1).unwrap();
+            screen.update(9, y, 1).unwrap();
+        }
+        screen.update(paddle, 7, 3).unwrap();
+        screen
+    }
+
+    fn move_ball(screen : &mut Screen, from : Pos, to : Pos) {
+        screen.update(from.0, from.1, 0).unwrap();
+        screen.update(to.0, to.1, 4).unwrap();
+    }
+
+    #[test]
+    fn predictive_heads_to_where_the_ball_lands() {
+        let mut screen = arena(5);
+        let mut controller = Predictive::new();
+
+        // Without a previous frame there is no velocity, so it follows the ball
+        screen.update(2, 2, 4).unwrap();
+        assert_eq!(controller.next_action(&screen), Action::Move(Joystick::Left));
+
+        // Going down and right it lands on 6, right of the paddle, even though
+        // the ball is left of it now
+        move_ball(&mut screen, (2, 2), (3, 3));
+        assert_eq!(controller.next_action(&screen), Action::Move(Joystick::Right));
+        assert_eq!(Autopilot.next_action(&screen), Action::Move(Joystick::Left));
+    }
+
+    #[test]
+    fn predictive_bounces_off_walls() {
+        let screen = arena(5);
+
+        // Straight down and right it would land inside the wall at 9
+        assert_eq!(Predictive::landing(&screen, (7, 4), (1, 1), 7), Some(7));
+        // Bouncing off the top wall before coming down
+        assert_eq!(Predictive::landing(&screen, (2, 2), (1, -1), 7), Some(8));
+    }
+
+    #[test]
+    fn predictive_bounces_off_blocks_once() {
+        let mut screen = arena(5);
+        screen.update(4, 3, 2).unwrap();
+
+        // The block sends the ball back down and left
+        assert_eq!(Predictive::landing(&screen, (3, 4), (1, -1), 7), Some(1));
+    }
+
+    #[test]
+    fn predictive_waits_without_a_ball() {
+        let screen = arena(5);
+        assert_eq!(Predictive::new().next_action(&screen), Action::Move(Joystick::Neutral));
+    }
+
+    fn script(name : &str, contents : &str) -> Result<Scripted, String> {
+        let path = env::temp_dir().join(format!("day13-{}-{}.txt", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let scripted = Scripted::from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        scripted
+    }
+
+    #[test]
+    fn scripted_replays_moves_then_stops() {
+        let mut controller = script("moves", "-1, 0,1\n1\n").unwrap();
+        let screen = Screen::new();
+
+        let actions : Vec<Action> = (0..5).map(|_| controller.next_action(&screen)).collect();
+        assert_eq!(actions, [
+            Action::Move(Joystick::Left),
+            Action::Move(Joystick::Neutral),
+            Action::Move(Joystick::Right),
+            Action::Move(Joystick::Right),
+            Action::Stop,
+        ]);
+    }
+
+    #[test]
+    fn scripted_rejects_invalid_moves() {
+        let error = script("invalid", "0,2,1").err().unwrap();
+        assert!(error.starts_with("Invalid move \"2\""), "{}", error);
+    }
+}
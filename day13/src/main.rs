@@ -1,7 +1,23 @@
-use std::io::{self, Read};
-use std::fs::File;
-use std::collections::HashMap;
-use std::f64;
+use std::env;
+use std::fs;
+use std::process;
+
+mod cabinet;
+mod controller;
+mod render;
+mod screen;
+mod stats;
+mod terminal;
+
+use cabinet::Cabinet;
+use controller::{Controller, Human, Autopilot, Predictive, Scripted};
+use render::Renderer;
+use screen::Tile;
+use stats::Stats;
+use terminal::{Keyboard, RawMode};
+
+use intcode::loader::{self, Source};
+use intcode::patch::{self, Patch};
 
 type Num = i64;
 type Pos = (Num, Num);
@@ -14,14 +30,6 @@ struct Instruction {
     mode3 : Num,
 }
 
-#[derive(Debug)]
-enum Direction {
-    N,
-    S,
-    E,
-    W
-}
-
 fn decode (i : Num) -> Instruction {
     let mut i = i;
     
@@ -39,7 +47,7 @@ fn decode (i : Num) -> Instruction {
     
     let mode3 = i % 10;
     
-    return Instruction {
+    Instruction {
         opcode,
         mode1,
         mode2, 
@@ -179,192 +187,227 @@ fn change_relative_base(memory : &mut Vec<Num>,
                         instruction : Instruction,
                         relative_base : &mut Num) -> Option<usize> {
     let op1 = get_value(memory, pc+1, instruction.mode1, *relative_base);
-    *relative_base = (*relative_base) + op1; 
+    *relative_base += op1; 
     Some(pc+2)                        
 }
 
 fn read_input(filename : &str) -> Vec<Num> {
-    let mut contents = String::new();
-
-    File::open(filename)
-        .unwrap()
-        .read_to_string(&mut contents)
-        .unwrap();
-
-    contents.split(',')
-            .map(|n| Num::from_str_radix(&n.trim(), 10)
-                     .unwrap()
-                )
-            .collect()    
+    match loader::load(&Source::from_arg(filename)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", filename, e);
+            process::exit(1);
+        }
+    }
 }
 
-fn print_panels(panels : &HashMap<Pos, Num>) {
-    let size : i64 = (panels.len()/3) as i64;
-    let side = (size * 2 + 1) as usize;
- 
-    let mut grid = vec![vec![0; side]; side];
+struct Options {
+    controller : ControllerKind,
+    render : bool,
+    fps : u32,
+    record : Option<String>,
+    headless : bool,
+    free_play : bool,
+    dump : Option<String>,
+    patches : Vec<Patch>,
+}
 
-    for entry in panels {
-        let pos = entry.0;
-        let col = entry.1;
+// How far back the rewind key goes, and how many times in a row
+const REWIND_SECONDS : u32 = 3;
+const REWIND_SNAPSHOTS : usize = 20;
 
-        if *col == 1 {
-            let pos_x_in_grid = pos.0 + size;
-            let pos_y_in_grid = size - pos.1;
+enum ControllerKind {
+    Human,
+    Follow,
+    Predict,
+    Keyboard,
+    Script(String),
+}
 
-            grid[pos_x_in_grid as usize][pos_y_in_grid as usize] = 1;
+fn parse_options() -> Result<Options, String> {
+    let mut kind = ControllerKind::Human;
+    let mut render = false;
+    let mut fps = 30;
+    let mut record = None;
+    let mut headless = false;
+    let mut free_play = true;
+    let mut dump = None;
+    let mut patches = Vec::new();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--controller" => {
+                kind = match args.next().as_deref() {
+                    Some("human") => ControllerKind::Human,
+                    Some("follow") => ControllerKind::Follow,
+                    Some("predict") => ControllerKind::Predict,
+                    Some("keyboard") => ControllerKind::Keyboard,
+                    other => return Err(format!("Unknown controller {:?}", other)),
+                };
+            },
+            "--script" => {
+                let file = args.next().ok_or("--script needs a file name")?;
+                kind = ControllerKind::Script(file);
+            },
+            "--replay" => {
+                let file = args.next().ok_or("--replay needs a file name")?;
+                kind = ControllerKind::Script(file);
+                render = true;
+            },
+            "--record" => {
+                let file = args.next().ok_or("--record needs a file name")?;
+                record = Some(file);
+            },
+            "--dump" => {
+                let directory = args.next().ok_or("--dump needs a directory")?;
+                dump = Some(directory);
+            },
+            "--patch" => {
+                let text = args.next().ok_or("--patch needs ADDRESS=VALUE pairs")?;
+                patches.extend(patch::parse(&text).map_err(|e| e.to_string())?);
+            },
+            "--render" => render = true,
+            "--headless" => headless = true,
+            "--no-free-play" => free_play = false,
+            "--fps" => {
+                let value = args.next().ok_or("--fps needs a value")?;
+                fps = match value.parse() {
+                    Ok(fps) if fps > 0 => fps,
+                    _ => return Err(format!("Invalid frame rate {:?}", value)),
+                };
+            },
+            _ => return Err(format!("Unknown argument {:?}", arg)),
         }
-    
     }
-    
-    for i in 0..size*2+1 {
-        for j in 0..size*2+1 {
-            print!("{}",grid[i as usize][j as usize]);
+
+    if headless {
+        // Nobody is watching, so nobody can play either
+        if let ControllerKind::Human | ControllerKind::Keyboard = kind {
+            kind = ControllerKind::Predict;
         }
-        println!("");
+        render = false;
+    } else if let ControllerKind::Human | ControllerKind::Keyboard = kind {
+        // A human needs to see what is going on
+        render = true;
     }
+
+    Ok(Options { controller : kind, render, fps, record, headless, free_play, dump, patches })
 }
 
-fn count_panels(panels : &HashMap<Pos, Num>) -> usize {
-    panels.iter().filter(|(_, &p)| p == 2).count()
+// A cabinet dumping its state every frame if asked to
+fn new_cabinet(memory : Vec<Num>, options : &Options) -> Cabinet {
+    let mut cabinet = Cabinet::new(memory);
+    if let Some(directory) = &options.dump {
+        if let Err(e) = fs::create_dir_all(directory) {
+            eprintln!("Could not create {}: {}", directory, e);
+            process::exit(1);
+        }
+        cabinet.enable_dump(directory);
+    }
+    cabinet
 }
 
-fn print_grid(grid : [[Num; 40]; 20]) {
-    for i in 0..grid.len() {
-        for j in 0..grid[i].len() {
-            print!("{:?}", grid[i][j]);
+// Saves the moves made so far, if asked to, so --replay can play them back
+fn write_recording(cabinet : &Cabinet, options : &Options) {
+    if let Some(file) = &options.record {
+        let moves : Vec<String> = cabinet.inputs().iter()
+            .map(|joystick| joystick.value().to_string())
+            .collect();
+        if let Err(e) = fs::write(file, moves.join("\n") + "\n") {
+            eprintln!("Could not write the recording to {}: {}", file, e);
         }
-        println!();
     }
 }
 
 fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("Usage: day13 [--controller human|keyboard|follow|predict] [--script FILE]\n\
+                   \x20            [--replay FILE] [--record FILE] [--render] [--fps N]\n\
+                   \x20            [--headless] [--no-free-play] [--dump DIRECTORY]\n\
+                   \x20            [--patch ADDRESS=VALUE,...]");
+        process::exit(1);
+    });
+
+    let mut controller : Box<dyn Controller> = match &options.controller {
+        ControllerKind::Human => Box::new(Human),
+        ControllerKind::Follow => Box::new(Autopilot),
+        ControllerKind::Predict => Box::new(Predictive::new()),
+        ControllerKind::Keyboard => Box::new(Keyboard::new(options.fps)),
+        ControllerKind::Script(file) => match Scripted::from_file(file) {
+            Ok(scripted) => Box::new(scripted),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+    };
+
     let mut memory = read_input("input.txt");
 
-    // Set quarters to play free
-    memory[0] = 2;
+    let mut patches = Vec::new();
+    if options.free_play {
+        // Set quarters to play free
+        patches.push(Patch { address : 0, value : 2 });
+    }
+    patches.extend(&options.patches);
+    if let Err(e) = patch::apply(&mut memory, &patches) {
+        eprintln!("Could not patch the program: {}", e);
+        process::exit(1);
+    }
 
-    println!("Welcome to the INTCODE computer!");
-    
-    let mut pc = 0;
-    let mut relative_base = 0;
-    let mut out_buffer = Vec::with_capacity(3);
+    if options.headless {
+        let mut stats = Stats::new(controller);
+        let mut cabinet = new_cabinet(memory, &options);
 
-    let mut grid = [[0; 40]; 20];
-    let mut score = 0;
-    
-    while pc < memory.len() {
-        let instruction = decode(memory[pc]);
-        let new_pc = match instruction.opcode {
-            99 => None,
-            1 | 2 => fetch_operands_and_store_result(&mut memory, 
-                pc, instruction, relative_base),
-            3 => {
-                let mut buffer = String::new();
-    
-                println!("Please input a value");
-                io::stdin().read_line(&mut buffer).unwrap();
-    
-                let result = Num::from_str_radix(&buffer.trim(), 10).unwrap();
-                input(&mut memory, pc, instruction, relative_base, result)
-            },
-            4 => {
-                let mut out = 0; 
-                let new_pc = output(&mut memory, pc, instruction, relative_base, &mut out);
-                
-                out_buffer.push(out);
-
-                if out_buffer.len() == 3 {
-                    if out_buffer[0] == -1 && out_buffer[1] == 0 {
-                        score = out_buffer[2];
-                    } else {
-                        grid[out_buffer[1] as usize][out_buffer[0] as usize] = out_buffer[2];
-                    }  
-                    out_buffer.clear();
-
-                    // print!("\x1B[2J");
-                    println!("Score {:?}", score);
-                    print_grid(grid);
-                }
-
-                new_pc
-            },
-            5 | 6 => jump_if(&mut memory, pc, instruction, relative_base),
-            7 | 8 => comparison(&mut memory, pc, instruction, 
-                relative_base),
-            9 => change_relative_base(&mut memory, pc, instruction, 
-                &mut relative_base),
-            _ => panic!("Unexpected opcode in instruction {:?}", instruction),
-        };
-
-        match new_pc {
-            Some(new_pc) => {pc = new_pc;}
-            None => {
-                println!("Halt!"); 
-                return;
-            }
+        let result = cabinet.run(&mut stats, None);
+        write_recording(&cabinet, &options);
+        if let Err(e) = result {
+            eprintln!("Bad output from the cabinet: {}", e);
+            process::exit(1);
         }
+        println!("{}", stats.report(cabinet.screen(), options.free_play));
+        return;
     }
-}
 
-// fn main() {
-//     let mut memory = read_input("input.txt");
+    println!("Welcome to the INTCODE computer!");
 
-//     println!("Welcome to the INTCODE computer!");
-    
-//     let mut pc = 0;
-//     let mut relative_base = 0;
-//     let mut curr_input;
-//     let mut out_buffer = Vec::with_capacity(3);
+    let mut cabinet = new_cabinet(memory, &options);
+    let mut renderer = if options.render { Some(Renderer::new(options.fps)) } else { None };
 
-//     let mut position = (0, 0);
-//     let mut direction = Direction::N;
+    // Arrows or a/d move, p pauses, r rewinds and q or Ctrl-C quits
+    let raw_mode = match options.controller {
+        ControllerKind::Keyboard => match RawMode::enable() {
+            Ok(raw_mode) => {
+                cabinet.enable_rewind((REWIND_SECONDS * options.fps) as usize, REWIND_SNAPSHOTS);
+                Some(raw_mode)
+            },
+            Err(e) => {
+                eprintln!("Could not set up the terminal: {}", e);
+                process::exit(1);
+            }
+        },
+        _ => None,
+    };
 
-//     let mut panels : HashMap<Pos, Num> = HashMap::new();
-//     // panels.insert((0,0), 1);
-    
-//     while pc < memory.len() {
-//         let instruction = decode(memory[pc]);
-//         let new_pc = match instruction.opcode {
-//             99 => None,
-//             1 | 2 => fetch_operands_and_store_result(&mut memory, 
-//                 pc, instruction, relative_base),
-//             3 => {
-//                 curr_input = match panels.get(&position) {
-//                     Some(color) => *color,
-//                     None => 0,
-//                 };
-//                 input(&mut memory, pc, instruction, relative_base, curr_input)
-//             },
-//             4 => {
-//                 let mut out = 0; 
-//                 let new_pc = output(&mut memory, pc, instruction, relative_base, &mut out);
-                
-//                 out_buffer.push(out);
-
-//                 if out_buffer.len() == 3 {
-//                     panels.insert((out_buffer[0], out_buffer[1]), out_buffer[2]);
-//                     out_buffer.clear();
-//                 }
-
-//                 new_pc
-//             },
-//             5 | 6 => jump_if(&mut memory, pc, instruction, relative_base),
-//             7 | 8 => comparison(&mut memory, pc, instruction, 
-//                 relative_base),
-//             9 => change_relative_base(&mut memory, pc, instruction, 
-//                 &mut relative_base),
-//             _ => panic!("Unexpected opcode in instruction {:?}", instruction),
-//         };
-//         match new_pc {
-//             Some(new_pc) => {pc = new_pc;}
-//             None => {
-//                 println!("Halt!");
-//                 // println!("Panels {:?}", panels);
-//                 println!("Number of unique panels {:?}", panels.len());
-//                 println!("Number of panels equal to a block {:?}", count_panels(&panels)); 
-//                 return;
-//             }
-//         }
-//     }
-// }
\ No newline at end of file
+    let result = cabinet.run(controller.as_mut(), renderer.as_mut());
+    if let Some(renderer) = renderer.as_mut() {
+        renderer.finish();
+    }
+    drop(raw_mode);
+
+    write_recording(&cabinet, &options);
+
+    match result {
+        Ok(score) => {
+            println!("Halt!");
+            println!("Blocks left {:?}", cabinet.screen().count(Tile::Block));
+            println!("Final score {:?}", score);
+        },
+        Err(e) => {
+            eprintln!("Bad output from the cabinet: {}", e);
+            process::exit(1);
+        }
+    }
+}
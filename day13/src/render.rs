@@ -0,0 +1,179 @@
+use std::io::{self, Stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Num;
+use crate::screen::{Screen, Tile};
+
+// Draws the screen on an ANSI terminal, only touching the cells that changed
+pub struct Renderer<W : Write = Stdout> {
+    out : W,
+    drawn : Vec<Vec<Option<Tile>>>,
+    status : Option<String>,
+    frame_time : Duration,
+    last_frame : Option<Instant>,
+}
+
+fn glyph(tile : Tile) -> &'static str {
+    match tile {
+        Tile::Empty => " ",
+        Tile::Wall => "\x1B[37m█",
+        Tile::Block => "\x1B[33m▒",
+        Tile::Paddle => "\x1B[36m═",
+        Tile::Ball => "\x1B[1;31m●",
+    }
+}
+
+impl Renderer {
+    pub fn new(fps : u32) -> Renderer {
+        Renderer::with_output(io::stdout(), fps)
+    }
+}
+
+impl<W : Write> Renderer<W> {
+    pub fn with_output(out : W, fps : u32) -> Renderer<W> {
+        Renderer {
+            out,
+            drawn : Vec::new(),
+            status : None,
+            frame_time : Duration::from_secs(1) / fps.max(1),
+            last_frame : None,
+        }
+    }
+
+    // Forgets what is on the terminal, so the next frame is drawn from scratch
+    pub fn invalidate(&mut self) {
+        self.drawn.clear();
+        self.status = None;
+    }
+
+    pub fn draw(&mut self, screen : &Screen) {
+        // Cap the frame rate by waiting for the rest of the frame time
+        if let Some(last_frame) = self.last_frame {
+            let elapsed = last_frame.elapsed();
+            if elapsed < self.frame_time {
+                thread::sleep(self.frame_time - elapsed);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+
+        let mut frame = String::new();
+
+        if self.drawn.is_empty() {
+            // Clear the terminal and hide the cursor
+            frame.push_str("\x1B[2J\x1B[?25l");
+        }
+
+        self.drawn.resize(screen.height(), Vec::new());
+        for (y, line) in self.drawn.iter_mut().enumerate() {
+            line.resize(screen.width(), None);
+
+            for (x, cell) in line.iter_mut().enumerate() {
+                let tile = screen.get(x as Num, y as Num);
+                if *cell != Some(tile) {
+                    frame.push_str(&format!("\x1B[{};{}H{}\x1B[0m", y + 1, x + 1, glyph(tile)));
+                    *cell = Some(tile);
+                }
+            }
+        }
+
+        let status = format!("Score: {:<8} Blocks: {:<4}", screen.score(), screen.count(Tile::Block));
+        if self.status.as_ref() != Some(&status) {
+            frame.push_str(&format!("\x1B[{};1H\x1B[2K{}", screen.height() + 2, status));
+            self.status = Some(status);
+        }
+
+        // Leave the cursor under the status line, clearing whatever was typed there
+        frame.push_str(&format!("\x1B[{};1H\x1B[J", screen.height() + 3));
+
+        self.write_out(frame.as_bytes());
+    }
+
+    // Shows the cursor again
+    pub fn finish(&mut self) {
+        self.write_out(b"\x1B[0m\x1B[?25h");
+    }
+
+    // Like print!, panics if the output is gone
+    fn write_out(&mut self, bytes : &[u8]) {
+        self.out.write_all(bytes).and_then(|_| self.out.flush()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn(renderer : &mut Renderer<Vec<u8>>, screen : &Screen) -> String {
+        renderer.out.clear();
+        renderer.draw(screen);
+        String::from_utf8(renderer.out.clone()).unwrap()
+    }
+
+    fn screen() -> Screen {
+        let mut screen = Screen::new();
+        screen.update(0, 0, 1).unwrap();
+        screen.update(1, 0, 2).unwrap();
+        screen.update(1, 1, 4).unwrap();
+        screen
+    }
+
+    #[test]
+    fn first_frame_draws_everything() {
+        let mut renderer = Renderer::with_output(Vec::new(), 1000);
+
+        assert_eq!(drawn(&mut renderer, &screen()), "\x1B[2J\x1B[?25l\
+            \x1B[1;1H\x1B[37m█\x1B[0m\x1B[1;2H\x1B[33m▒\x1B[0m\
+            \x1B[2;1H \x1B[0m\x1B[2;2H\x1B[1;31m●\x1B[0m\
+            \x1B[4;1H\x1B[2KScore: 0        Blocks: 1   \
+            \x1B[5;1H\x1B[J");
+    }
+
+    #[test]
+    fn only_changed_cells_are_drawn_again() {
+        let mut renderer = Renderer::with_output(Vec::new(), 1000);
+        let mut screen = screen();
+        drawn(&mut renderer, &screen);
+
+        // Nothing changed, only the cursor moves back under the status line
+        assert_eq!(drawn(&mut renderer, &screen), "\x1B[5;1H\x1B[J");
+
+        screen.update(1, 0, 0).unwrap();
+        assert_eq!(drawn(&mut renderer, &screen), "\x1B[1;2H \x1B[0m\
+            \x1B[4;1H\x1B[2KScore: 0        Blocks: 0   \
+            \x1B[5;1H\x1B[J");
+
+        // The score changes the status line alone
+        screen.update(-1, 0, 40).unwrap();
+        assert_eq!(drawn(&mut renderer, &screen), "\x1B[4;1H\x1B[2KScore: 40       Blocks: 0   \x1B[5;1H\x1B[J");
+    }
+
+    #[test]
+    fn invalidating_draws_everything_again() {
+        let mut renderer = Renderer::with_output(Vec::new(), 1000);
+        let first = drawn(&mut renderer, &screen());
+
+        renderer.invalidate();
+        assert_eq!(drawn(&mut renderer, &screen()), first);
+    }
+
+    #[test]
+    fn frame_rate_is_capped() {
+        let mut renderer = Renderer::with_output(Vec::new(), 20);
+        let screen = screen();
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            renderer.draw(&screen);
+        }
+        // The first frame doesn't wait, the other two wait 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn finishing_shows_the_cursor() {
+        let mut renderer = Renderer::with_output(Vec::new(), 1000);
+        renderer.finish();
+        assert_eq!(renderer.out, b"\x1B[0m\x1B[?25h");
+    }
+}
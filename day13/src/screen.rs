@@ -0,0 +1,215 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Num, Pos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+#[derive(Debug)]
+pub enum ScreenError {
+    UnknownTile(Num),
+    InvalidPosition(Pos),
+}
+
+// Screen that grows to fit whatever the cabinet draws on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screen {
+    tiles : Vec<Vec<Tile>>,
+    score : Num,
+    ball : Option<Pos>,
+    paddle : Option<Pos>,
+}
+
+impl Tile {
+    pub const ALL : [Tile; 5] = [Tile::Empty, Tile::Wall, Tile::Block, Tile::Paddle, Tile::Ball];
+}
+
+impl TryFrom<Num> for Tile {
+    type Error = ScreenError;
+
+    fn try_from(id : Num) -> Result<Tile, ScreenError> {
+        match id {
+            0 => Ok(Tile::Empty),
+            1 => Ok(Tile::Wall),
+            2 => Ok(Tile::Block),
+            3 => Ok(Tile::Paddle),
+            4 => Ok(Tile::Ball),
+            _ => Err(ScreenError::UnknownTile(id)),
+        }
+    }
+}
+
+impl fmt::Display for ScreenError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScreenError::UnknownTile(id) => write!(f, "unknown tile id {}", id),
+            ScreenError::InvalidPosition((x, y)) => write!(f, "invalid screen position ({}, {})", x, y),
+        }
+    }
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen {
+            tiles : Vec::new(),
+            score : 0,
+            ball : None,
+            paddle : None,
+        }
+    }
+
+    // Applies one (x, y, value) triple from the cabinet output
+    pub fn update(&mut self, x : Num, y : Num, value : Num) -> Result<(), ScreenError> {
+        if x == -1 && y == 0 {
+            self.score = value;
+            return Ok(());
+        }
+
+        let tile = Tile::try_from(value)?;
+        if x < 0 || y < 0 {
+            return Err(ScreenError::InvalidPosition((x, y)));
+        }
+
+        let (col, row) = (x as usize, y as usize);
+        if row >= self.tiles.len() {
+            let width = self.width();
+            self.tiles.resize(row + 1, vec![Tile::Empty; width]);
+        }
+        if col >= self.width() {
+            for line in self.tiles.iter_mut() {
+                line.resize(col + 1, Tile::Empty);
+            }
+        }
+        self.tiles[row][col] = tile;
+
+        match tile {
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::Paddle => self.paddle = Some((x, y)),
+            _ => {
+                if self.ball == Some((x, y)) {
+                    self.ball = None;
+                }
+                if self.paddle == Some((x, y)) {
+                    self.paddle = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, x : Num, y : Num) -> Tile {
+        if x < 0 || y < 0 {
+            return Tile::Empty;
+        }
+        self.tiles.get(y as usize)
+            .and_then(|line| line.get(x as usize))
+            .copied()
+            .unwrap_or(Tile::Empty)
+    }
+
+    pub fn width(&self) -> usize {
+        self.tiles.first().map_or(0, |line| line.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn score(&self) -> Num {
+        self.score
+    }
+
+    pub fn ball(&self) -> Option<Pos> {
+        self.ball
+    }
+
+    pub fn paddle(&self) -> Option<Pos> {
+        self.paddle
+    }
+
+    pub fn count(&self, tile : Tile) -> usize {
+        self.tiles.iter().flatten().filter(|&&t| t == tile).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_come_from_their_ids() {
+        for (id, &tile) in Tile::ALL.iter().enumerate() {
+            assert_eq!(Tile::try_from(id as Num).unwrap(), tile);
+        }
+        assert!(matches!(Tile::try_from(5), Err(ScreenError::UnknownTile(5))));
+        assert!(matches!(Tile::try_from(-1), Err(ScreenError::UnknownTile(-1))));
+    }
+
+    #[test]
+    fn grows_to_fit_the_drawing() {
+        let mut screen = Screen::new();
+        assert_eq!((screen.width(), screen.height()), (0, 0));
+
+        screen.update(2, 0, 1).unwrap();
+        assert_eq!((screen.width(), screen.height()), (3, 1));
+        screen.update(0, 3, 2).unwrap();
+        assert_eq!((screen.width(), screen.height()), (3, 4));
+        screen.update(4, 1, 2).unwrap();
+        assert_eq!((screen.width(), screen.height()), (5, 4));
+
+        // Every row grew, and the tiles drawn before stay where they were
+        assert_eq!(screen.get(2, 0), Tile::Wall);
+        assert_eq!(screen.get(0, 3), Tile::Block);
+        assert_eq!(screen.get(4, 3), Tile::Empty);
+        assert_eq!(screen.get(9, 9), Tile::Empty);
+        assert_eq!(screen.count(Tile::Block), 2);
+    }
+
+    #[test]
+    fn rejects_unknown_tiles_and_negative_positions() {
+        let mut screen = Screen::new();
+
+        assert!(matches!(screen.update(0, 0, 7), Err(ScreenError::UnknownTile(7))));
+        assert!(matches!(screen.update(-2, 0, 1), Err(ScreenError::InvalidPosition((-2, 0)))));
+        assert!(matches!(screen.update(-1, 1, 1), Err(ScreenError::InvalidPosition((-1, 1)))));
+        assert_eq!((screen.width(), screen.height()), (0, 0));
+        assert_eq!(ScreenError::UnknownTile(7).to_string(), "unknown tile id 7");
+        assert_eq!(ScreenError::InvalidPosition((-2, 0)).to_string(), "invalid screen position (-2, 0)");
+    }
+
+    #[test]
+    fn score_is_written_at_minus_one_zero() {
+        let mut screen = Screen::new();
+
+        screen.update(-1, 0, 12345).unwrap();
+        assert_eq!(screen.score(), 12345);
+        // Any value is a score, not just the tile ids
+        screen.update(-1, 0, 99).unwrap();
+        assert_eq!(screen.score(), 99);
+        assert_eq!((screen.width(), screen.height()), (0, 0));
+    }
+
+    #[test]
+    fn tracks_the_ball_and_paddle() {
+        let mut screen = Screen::new();
+
+        screen.update(3, 4, 4).unwrap();
+        screen.update(2, 5, 3).unwrap();
+        assert_eq!(screen.ball(), Some((3, 4)));
+        assert_eq!(screen.paddle(), Some((2, 5)));
+
+        screen.update(3, 4, 0).unwrap();
+        screen.update(4, 3, 4).unwrap();
+        assert_eq!(screen.ball(), Some((4, 3)));
+        screen.update(2, 5, 0).unwrap();
+        assert_eq!(screen.paddle(), None);
+    }
+}
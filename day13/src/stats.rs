@@ -0,0 +1,99 @@
+use crate::controller::{Action, Controller};
+use crate::screen::{Screen, Tile};
+
+// Wraps a controller to keep track of how the game is going frame by frame
+pub struct Stats {
+    controller : Box<dyn Controller>,
+    frames : usize,
+    initial_blocks : Option<usize>,
+    // (frame, total blocks broken so far), every time a block breaks
+    broken : Vec<(usize, usize)>,
+}
+
+impl Stats {
+    pub fn new(controller : Box<dyn Controller>) -> Stats {
+        Stats {
+            controller,
+            frames : 0,
+            initial_blocks : None,
+            broken : Vec::new(),
+        }
+    }
+
+    fn count_broken(&mut self, screen : &Screen) {
+        let blocks = screen.count(Tile::Block);
+        let initial = *self.initial_blocks.get_or_insert(blocks);
+        let broken = initial.saturating_sub(blocks);
+
+        if broken != self.broken.last().map_or(0, |&(_, broken)| broken) {
+            self.broken.push((self.frames, broken));
+        }
+    }
+
+    // Final statistics as JSON
+    pub fn report(&mut self, screen : &Screen, free_play : bool) -> String {
+        self.count_broken(screen);
+
+        let tiles : Vec<String> = Tile::ALL.iter()
+            .map(|&tile| format!("\"{}\": {}", format!("{:?}", tile).to_lowercase(), screen.count(tile)))
+            .collect();
+        let broken : Vec<String> = self.broken.iter()
+            .map(|(frame, broken)| format!("[{}, {}]", frame, broken))
+            .collect();
+
+        format!("{{\n  \"free_play\": {},\n  \"frames\": {},\n  \"score\": {},\n  \"tiles\": {{ {} }},\n  \
+                 \"initial_blocks\": {},\n  \"blocks_broken\": [{}]\n}}",
+                free_play,
+                self.frames,
+                screen.score(),
+                tiles.join(", "),
+                self.initial_blocks.unwrap_or(0),
+                broken.join(", "))
+    }
+}
+
+impl Controller for Stats {
+    fn next_action(&mut self, screen : &Screen) -> Action {
+        self.count_broken(screen);
+        self.frames += 1;
+        self.controller.next_action(screen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Joystick;
+
+    struct Still;
+
+    impl Controller for Still {
+        fn next_action(&mut self, _screen : &Screen) -> Action {
+            Action::Move(Joystick::Neutral)
+        }
+    }
+
+    #[test]
+    fn reports_tiles_frames_and_blocks_broken_over_time() {
+        let mut screen = Screen::new();
+        for x in 0..3 {
+            screen.update(x, 0, 2).unwrap();
+        }
+        screen.update(1, 1, 4).unwrap();
+        screen.update(1, 2, 3).unwrap();
+        let mut stats = Stats::new(Box::new(Still));
+
+        assert_eq!(stats.next_action(&screen), Action::Move(Joystick::Neutral));
+        screen.update(0, 0, 0).unwrap();
+        stats.next_action(&screen);
+        // Nothing broke in this frame, so it isn't listed
+        stats.next_action(&screen);
+        screen.update(1, 0, 0).unwrap();
+        screen.update(2, 0, 0).unwrap();
+        screen.update(-1, 0, 50).unwrap();
+
+        assert_eq!(stats.report(&screen, true), "{\n  \"free_play\": true,\n  \"frames\": 3,\n  \"score\": 50,\n  \
+                   \"tiles\": { \"empty\": 7, \"wall\": 0, \"block\": 0, \"paddle\": 1, \"ball\": 1 },\n  \
+                   \"initial_blocks\": 3,\n  \"blocks_broken\": [[1, 1], [3, 3]]\n}");
+    }
+}
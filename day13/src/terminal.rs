@@ -0,0 +1,194 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::controller::{Action, Controller, Joystick};
+use crate::screen::Screen;
+
+// Puts the terminal in raw, non-blocking mode until dropped
+pub struct RawMode {
+    saved : String,
+}
+
+fn stty(args : &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        // Reads return immediately, with or without a key press. Ctrl-C is
+        // read as a key too, so quitting always goes through the drop below
+        // and the terminal gets its echo back.
+        stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "0"])?;
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Left,
+    Right,
+    Pause,
+    Rewind,
+    Quit,
+}
+
+// The keys in bytes read from the terminal, and how many bytes they took.
+// Escape sequences are taken whole, up to their final byte, so keys without
+// a binding like the up arrow don't leak letters that are bound on their
+// own. A sequence cut off at the end is left over for the next read.
+fn parse_keys(bytes : &[u8]) -> (Vec<Key>, usize) {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..] == [0x1B] {
+            break;
+        }
+        if let [0x1B, b'[', rest @ ..] = &bytes[i..] {
+            // Parameters and intermediates are 0x20 to 0x3F, the final byte
+            // is 0x40 to 0x7E
+            let length = match rest.iter().position(|byte| (0x40..=0x7E).contains(byte)) {
+                Some(length) => length,
+                None => break,
+            };
+            match rest[length] {
+                b'D' => keys.push(Key::Left),
+                b'C' => keys.push(Key::Right),
+                _ => (),
+            }
+            i += 2 + length + 1;
+            continue;
+        }
+
+        match bytes[i] {
+            b'a' | b'A' => keys.push(Key::Left),
+            b'd' | b'D' => keys.push(Key::Right),
+            b'p' | b'P' | b' ' => keys.push(Key::Pause),
+            b'r' | b'R' => keys.push(Key::Rewind),
+            b'q' | b'Q' | 0x03 => keys.push(Key::Quit),
+            _ => (),
+        }
+        i += 1;
+    }
+    (keys, i)
+}
+
+// Reads every key pressed since the last call, without waiting. Bytes of an
+// unfinished escape sequence stay in pending until the rest arrives.
+fn pressed_keys(pending : &mut Vec<u8>) -> Vec<Key> {
+    let mut buffer = [0; 64];
+    let read = io::stdin().read(&mut buffer).unwrap_or(0);
+    pending.extend_from_slice(&buffer[..read]);
+
+    let (keys, used) = parse_keys(pending);
+    pending.drain(..used);
+    keys
+}
+
+// Plays from the keyboard: the game advances one frame every tick, moving the
+// paddle according to the last arrow key pressed during that tick
+pub struct Keyboard {
+    tick : Duration,
+    next_frame : Option<Instant>,
+    paused : bool,
+    pending : Vec<u8>,
+}
+
+impl Keyboard {
+    pub fn new(fps : u32) -> Keyboard {
+        Keyboard {
+            tick : Duration::from_secs(1) / fps.max(1),
+            next_frame : None,
+            paused : false,
+            pending : Vec::new(),
+        }
+    }
+}
+
+impl Controller for Keyboard {
+    fn next_action(&mut self, _screen : &Screen) -> Action {
+        let mut joystick = Joystick::Neutral;
+
+        loop {
+            for key in pressed_keys(&mut self.pending) {
+                match key {
+                    Key::Left => joystick = Joystick::Left,
+                    Key::Right => joystick = Joystick::Right,
+                    Key::Pause => self.paused = !self.paused,
+                    Key::Rewind => return Action::Rewind,
+                    Key::Quit => return Action::Stop,
+                }
+            }
+
+            let now = Instant::now();
+            let next_frame = *self.next_frame.get_or_insert(now);
+            if !self.paused && now >= next_frame {
+                // Don't try to catch up on frames we were late for
+                let following = next_frame + self.tick;
+                self.next_frame = Some(if following < now { now } else { following });
+                return Action::Move(joystick);
+            }
+
+            // Keep polling the keyboard while we wait for the frame
+            thread::sleep(Duration::from_millis(5));
+            if self.paused {
+                self.next_frame = None;
+                joystick = Joystick::Neutral;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrows_and_letters() {
+        let keys = [
+            Key::Left, Key::Right, Key::Left, Key::Pause, Key::Right, Key::Pause, Key::Pause, Key::Rewind,
+            Key::Pause, Key::Quit, Key::Quit,
+        ];
+        assert_eq!(parse_keys(b"\x1B[D\x1B[Ca dP r q\x03"), (keys.to_vec(), 15));
+    }
+
+    #[test]
+    fn unbound_sequences_are_skipped_whole() {
+        // Up and down end in A and B, which mustn't read as letters
+        assert_eq!(parse_keys(b"\x1B[A\x1B[B"), (vec![], 6));
+        // Ctrl-left and ctrl-right still move
+        assert_eq!(parse_keys(b"\x1B[1;5D\x1B[1;5C"), (vec![Key::Left, Key::Right], 12));
+        assert_eq!(parse_keys(b"\x1B[5~d"), (vec![Key::Right], 5));
+        // Escape on its own isn't the start of a sequence
+        assert_eq!(parse_keys(b"\x1Bq"), (vec![Key::Quit], 2));
+    }
+
+    #[test]
+    fn cut_off_sequences_wait_for_the_rest() {
+        assert_eq!(parse_keys(b"a\x1B[1;5"), (vec![Key::Left], 1));
+        assert_eq!(parse_keys(b"d\x1B"), (vec![Key::Right], 1));
+
+        // The rest of the left arrow arriving in the next read
+        let mut pending = b"\x1B[".to_vec();
+        assert_eq!(parse_keys(&pending), (vec![], 0));
+        pending.push(b'D');
+        assert_eq!(parse_keys(&pending), (vec![Key::Left], 3));
+    }
+}
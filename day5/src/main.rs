@@ -1,5 +1,12 @@
-use std::io::{self, Read};
-use std::fs::File;
+// The interpreter is kept as it was first written
+#![allow(clippy::needless_return, clippy::ptr_arg, clippy::from_str_radix_10, clippy::needless_borrow)]
+
+use std::env;
+use std::io;
+use std::process;
+
+use intcode::loader::{self, Source};
+use intcode::patch::{self, Patch};
 
 #[derive(Debug)]
 struct Instruction {
@@ -126,22 +133,47 @@ fn comparison(memory : &mut Vec<i32>, pc : usize, instruction : Instruction) ->
 }
 
 fn read_input(filename : &str) -> Vec<i32> {
-    let mut contents = String::new();
-
-    File::open(filename)
-        .unwrap()
-        .read_to_string(&mut contents)
-        .unwrap();
-
-    contents.split(',')
-            .map(|n| i32::from_str_radix(&n.trim(), 10)
-                     .unwrap()
-                )
-            .collect()    
+    match loader::load(&Source::from_arg(filename)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", filename, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: day5 [--patch ADDRESS=VALUE,...]");
+    eprintln!("Runs the diagnostic program, with the patches written into it first.");
+    process::exit(1);
+}
+
+fn read_patches() -> Vec<Patch> {
+    let mut patches = Vec::new();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--patch" => match patch::parse(&args.next().unwrap_or_else(|| usage())) {
+                Ok(parsed) => patches.extend(parsed),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    usage();
+                }
+            },
+            _ => usage(),
+        }
+    }
+    patches
 }
 
 fn main() {
+    let patches = read_patches();
     let mut memory = read_input("input.txt");
+    if let Err(e) = patch::apply(&mut memory, &patches) {
+        eprintln!("Could not patch the program: {}", e);
+        process::exit(1);
+    }
 
     println!("Welcome to the INTCODE computer!");
     
@@ -0,0 +1,17 @@
+// The search over every phase setting sequence for the puzzle input, with the
+// amplifiers in a feedback loop
+
+use day7_2::best_feedback_signal;
+use intcode::bench;
+use intcode::loader;
+
+const INPUT : &str = include_str!("../input.txt");
+
+const ITERATIONS : usize = 50;
+
+fn main() {
+    let program = loader::parse(INPUT).unwrap();
+
+    let measurement = bench::measure("day7-2/feedback", ITERATIONS, || best_feedback_signal(&program));
+    bench::report(&[measurement]);
+}
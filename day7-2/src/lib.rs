@@ -0,0 +1,138 @@
+use std::collections::{HashSet, LinkedList};
+use std::error;
+use std::fmt;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread;
+
+use intcode::Num;
+use intcode::machine::{EmptyInput, Error, Machine};
+
+// Why the amplifiers couldn't produce a signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmplifierError {
+    Machine(Error),
+    // The last amplifier halted without a signal for the thrusters
+    NoOutput,
+}
+
+impl fmt::Display for AmplifierError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplifierError::Machine(e) => write!(f, "{}", e),
+            AmplifierError::NoOutput => write!(f, "an amplifier halted without output"),
+        }
+    }
+}
+
+impl error::Error for AmplifierError {}
+
+impl From<Error> for AmplifierError {
+    fn from(e : Error) -> AmplifierError {
+        AmplifierError::Machine(e)
+    }
+}
+
+// Runs one amplifier, waiting on the receiver for inputs and sending every
+// output along. Returns everything it output once it halts.
+fn run_code(memory : Vec<Num>, inputs : Receiver<Num>, outputs : Sender<Num>) -> Result<Vec<Num>, Error> {
+    let mut machine = Machine::new(memory).empty_input(EmptyInput::Block).connect(inputs, outputs);
+    machine.run()?;
+    Ok(machine.take_outputs())
+}
+
+fn generate_combinations(possible_inputs : HashSet<Num>) -> Vec<LinkedList<Num>> {
+    if !possible_inputs.is_empty() {
+        return possible_inputs.iter().flat_map(|input| {
+            let mut inputs_clon = possible_inputs.clone();
+            inputs_clon.remove(input);
+            
+            generate_combinations(inputs_clon).into_iter().map(move |mut comb| {
+                comb.push_front(*input);
+                comb
+            })
+        }).collect::<Vec<LinkedList<Num>>>();
+    }
+    vec![LinkedList::new()]
+}
+
+// Tries every phase setting sequence on the amplifiers wired in a feedback
+// loop and returns the highest signal with the sequence that produced it
+pub fn best_feedback_signal(initial_memory : &[Num]) -> Result<(Num, [Num; 5]), AmplifierError> {
+    let mut vals = HashSet::new();
+    vals.insert(5);
+    vals.insert(6);
+    vals.insert(7);
+    vals.insert(8);
+    vals.insert(9);
+    
+    // println!("{:?}", generate_combinations(vals));
+    let mut max = Num::MIN;
+    let mut ampl_seq = [5,6,7,8,9];
+    
+    // let thruster_seq
+    for mut possible_seq in generate_combinations(vals) {
+        // println!("Possible sequence {:?}", possible_seq);
+        let ampl_a = possible_seq.pop_front().unwrap();
+        let ampl_b = possible_seq.pop_front().unwrap();
+        let ampl_c = possible_seq.pop_front().unwrap();
+        let ampl_d = possible_seq.pop_front().unwrap();
+        let ampl_e = possible_seq.pop_front().unwrap();
+        
+        let (a_sender, a_receiver) = channel();
+        let (b_sender, b_receiver) = channel();
+        let (c_sender, c_receiver) = channel();
+        let (d_sender, d_receiver) = channel();
+        let (e_sender, e_receiver) = channel();
+
+        // Phase setting
+        a_sender.send(ampl_a).unwrap();
+        b_sender.send(ampl_b).unwrap();
+        c_sender.send(ampl_c).unwrap();
+        d_sender.send(ampl_d).unwrap();
+        e_sender.send(ampl_e).unwrap();
+        // First signal
+        a_sender.send(0).unwrap();
+
+        // println!("Sent phase settings and first signal");
+
+        let mem = initial_memory.to_vec();
+        let thread_a = thread::Builder::new().name("amplifier a".to_string()).spawn(move || {
+            run_code(mem, a_receiver, b_sender)
+        }).unwrap();
+
+        let mem = initial_memory.to_vec();
+        let thread_b = thread::Builder::new().name("amplifier b".to_string()).spawn(move || {
+            run_code(mem, b_receiver, c_sender)
+            // run_code(mem, b_receiver, a_sender)
+        }).unwrap();
+
+        let mem = initial_memory.to_vec();
+        let thread_c = thread::Builder::new().name("amplifier c".to_string()).spawn(move || {
+            run_code(mem, c_receiver, d_sender)
+        }).unwrap();
+
+        let mem = initial_memory.to_vec();
+        let thread_d = thread::Builder::new().name("amplifier d".to_string()).spawn(move || {
+            run_code(mem, d_receiver, e_sender)
+        }).unwrap();
+
+        let mem = initial_memory.to_vec();
+        let thread_e = thread::Builder::new().name("amplifier e".to_string()).spawn(move || {
+            run_code(mem, e_receiver, a_sender)
+        }).unwrap();
+        
+        thread_a.join().unwrap()?;
+        thread_b.join().unwrap()?;
+        thread_c.join().unwrap()?;
+        thread_d.join().unwrap()?;
+        // The last signal from e goes to the thrusters
+        let res = *thread_e.join().unwrap()?.last().ok_or(AmplifierError::NoOutput)?;
+
+        if res > max {
+            max = res;
+            ampl_seq = [ampl_a, ampl_b, ampl_c, ampl_d, ampl_e];
+            // ampl_seq = Some([ampl_a, ampl_b]);
+        }
+    }
+    Ok((max, ampl_seq))
+}
@@ -0,0 +1,16 @@
+// The search over every phase setting sequence for the puzzle input
+
+use day7::best_signal;
+use intcode::bench;
+use intcode::loader;
+
+const INPUT : &str = include_str!("../src/input.txt");
+
+const ITERATIONS : usize = 200;
+
+fn main() {
+    let program = loader::parse(INPUT).unwrap();
+
+    let measurement = bench::measure("day7/amplifiers", ITERATIONS, || best_signal(&program));
+    bench::report(&[measurement]);
+}
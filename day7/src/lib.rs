@@ -0,0 +1,95 @@
+use std::collections::{HashSet, LinkedList};
+use std::error;
+use std::fmt;
+
+use intcode::Num;
+use intcode::machine::{EmptyInput, Error, Machine};
+
+// Why the amplifiers couldn't produce a signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmplifierError {
+    Machine(Error),
+    // An amplifier halted without passing a signal on
+    NoOutput,
+}
+
+impl fmt::Display for AmplifierError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplifierError::Machine(e) => write!(f, "{}", e),
+            AmplifierError::NoOutput => write!(f, "an amplifier halted without output"),
+        }
+    }
+}
+
+impl error::Error for AmplifierError {}
+
+impl From<Error> for AmplifierError {
+    fn from(e : Error) -> AmplifierError {
+        AmplifierError::Machine(e)
+    }
+}
+
+// Runs one amplifier with the given inputs and returns the signal it passes
+// on. A program asking for more inputs than it was given fails instead of
+// waiting for them.
+fn run_code(memory : &[Num], inputs : Vec<Num>) -> Result<Num, AmplifierError> {
+    let mut machine = Machine::new(memory.to_vec()).empty_input(EmptyInput::Error);
+    for input in inputs {
+        machine.push_input(input);
+    }
+    machine.run()?;
+    machine.outputs().first().copied().ok_or(AmplifierError::NoOutput)
+}
+
+fn generate_combinations(possible_inputs : HashSet<Num>) -> Vec<LinkedList<Num>> {
+    if !possible_inputs.is_empty() {
+        return possible_inputs.iter().flat_map(|input| {
+            let mut inputs_clon = possible_inputs.clone();
+            inputs_clon.remove(input);
+            
+            generate_combinations(inputs_clon).into_iter().map(move |mut comb| {
+                comb.push_front(*input);
+                comb
+            })
+        }).collect::<Vec<LinkedList<Num>>>();
+    }
+    vec![LinkedList::new()]
+}
+
+// Tries every phase setting sequence on the amplifiers and returns the
+// highest signal with the sequence that produced it
+pub fn best_signal(initial_memory : &[Num]) -> Result<(Num, [Num; 5]), AmplifierError> {
+    let mut vals = HashSet::new();
+    vals.insert(0);
+    vals.insert(1);
+    vals.insert(2);
+    vals.insert(3);
+    vals.insert(4);
+    
+    // println!("{:?}", generate_combinations(vals));
+    let mut max = Num::MIN;
+    let mut ampl_seq = [0,1,2,3,4];
+    
+    // let thruster_seq
+    for mut possible_seq in generate_combinations(vals) {
+        
+        let ampl_a = possible_seq.pop_front().unwrap();
+        let ampl_b = possible_seq.pop_front().unwrap();
+        let ampl_c = possible_seq.pop_front().unwrap();
+        let ampl_d = possible_seq.pop_front().unwrap();
+        let ampl_e = possible_seq.pop_front().unwrap();
+        
+        let res_a = run_code(initial_memory, vec![ampl_a, 0])?;
+        let res_b = run_code(initial_memory, vec![ampl_b, res_a])?;
+        let res_c = run_code(initial_memory, vec![ampl_c, res_b])?;
+        let res_d = run_code(initial_memory, vec![ampl_d, res_c])?;
+        let res_e = run_code(initial_memory, vec![ampl_e, res_d])?;
+        
+        if res_e > max {
+            max = res_e;
+            ampl_seq = [ampl_a, ampl_b, ampl_c, ampl_d, ampl_e];
+        }
+    }
+    Ok((max, ampl_seq))
+}
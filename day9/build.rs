@@ -0,0 +1,31 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use intcode::loader;
+use intcode::transpile;
+
+// Programs compiled for the tests, which check them against the machine
+const FIXTURES : [(&str, &str); 4] = [
+    ("countdown", "fixtures/countdown.txt"),
+    ("jump_target", "fixtures/jump_target.txt"),
+    ("rewrites", "fixtures/rewrites.txt"),
+    ("diagnostic", "input_day5.txt"),
+];
+
+fn compile(source : &str, destination : &str) {
+    println!("cargo:rerun-if-changed={}", source);
+
+    let text = fs::read_to_string(source).unwrap_or_else(|e| panic!("Could not read {}: {}", source, e));
+    let program = loader::parse(&text).unwrap_or_else(|e| panic!("Could not parse {}: {}", source, e));
+    let destination = Path::new(&env::var("OUT_DIR").unwrap()).join(destination);
+    fs::write(destination, transpile::transpile(&program)).expect("Could not write the compiled program");
+}
+
+// Compiles BOOST to Rust ahead of time, for --compiled
+fn main() {
+    compile("input.txt", "boost.rs");
+    for (name, source) in &FIXTURES {
+        compile(source, &format!("{}.rs", name));
+    }
+}
@@ -0,0 +1,79 @@
+// Runs programs compiled by build.rs next to the machine, including ones that
+// rewrite their own code and have to fall back to the interpreter
+
+use intcode::Num;
+use intcode::loader;
+use intcode::machine::{Computer, Machine, Status};
+
+macro_rules! fixtures {
+    ($($name : ident),*) => {
+        $(
+            #[allow(clippy::all, unused)]
+            mod $name {
+                include!(concat!(env!("OUT_DIR"), "/", stringify!($name), ".rs"));
+            }
+        )*
+    };
+}
+
+fixtures!(countdown, jump_target, rewrites, diagnostic);
+
+// Words past the end of either memory read as zero
+fn assert_same_memory(compiled : &[Num], interpreted : &[Num]) {
+    let word = |memory : &[Num], address : usize| memory.get(address).copied().unwrap_or(0);
+    for address in 0..compiled.len().max(interpreted.len()) {
+        assert_eq!(word(compiled, address), word(interpreted, address), "memory at {}", address);
+    }
+}
+
+// Runs the compiled program and the machine on the same inputs, checks they
+// stop the same way with the same outputs and memory, and returns the
+// machine so the caller can look at it
+macro_rules! check {
+    ($module : ident, $source : expr, $inputs : expr, $interpreting : expr) => {{
+        let mut program = $module::Program::new();
+        let mut machine = Machine::new(loader::parse(include_str!($source)).unwrap());
+        for &input in $inputs.iter() {
+            program.push_input(input);
+            machine.push_input(input);
+        }
+
+        let status = program.run();
+        assert_eq!(status, machine.run());
+        assert_eq!(status, Ok(Status::Halted));
+        assert_eq!(program.outputs(), machine.outputs());
+        assert_same_memory(program.memory(), machine.memory());
+        assert_eq!(program.is_interpreting(), $interpreting);
+        machine
+    }};
+}
+
+#[test]
+fn straight_code_stays_compiled() {
+    let machine = check!(countdown, "../fixtures/countdown.txt", [] as [Num; 0], false);
+    assert_eq!(machine.outputs(), &[3, 2, 1]);
+}
+
+#[test]
+fn written_operands_stay_compiled() {
+    // Only a jump target is written, which the compiled code reads at run
+    // time. The new target skips the output.
+    let machine = check!(jump_target, "../fixtures/jump_target.txt", [] as [Num; 0], false);
+    assert!(machine.outputs().is_empty());
+}
+
+#[test]
+fn statically_rewritten_instruction_is_left_out() {
+    // The write into the output at 0 is visible before running, so that
+    // instruction is never compiled and the rest can stay compiled
+    let machine = check!(rewrites, "../fixtures/rewrites.txt", [] as [Num; 0], false);
+    assert_eq!(machine.outputs(), &[7, 20]);
+}
+
+#[test]
+fn diagnostic_program_matches_the_machine() {
+    // The thermal radiator test writes over compiled code through an address
+    // only known at run time, so from then on everything is interpreted
+    check!(diagnostic, "../input_day5.txt", [1], false);
+    check!(diagnostic, "../input_day5.txt", [5], true);
+}
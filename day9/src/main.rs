@@ -1,220 +1,230 @@
-use std::io::{self, Read};
-use std::fs::File;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::time::Duration;
+
+use intcode::Num;
+use intcode::loader::{self, Source};
+use intcode::coverage::Coverage;
+use intcode::machine::{Computer, Instruction, Limits, Machine, Status, Trace};
+use intcode::profile::Profiler;
+
+// input.txt as compiled by build.rs
+#[allow(clippy::all, unused)]
+mod boost {
+    include!(concat!(env!("OUT_DIR"), "/boost.rs"));
+}
 
-type Num = i64;
+#[cfg(test)]
+mod compiled;
 
-#[derive(Debug)]
-struct Instruction {
-    opcode : Num,
-    mode1 : Num,
-    mode2 : Num,
-    mode3 : Num,
-}
+// Ranges listed in the profile report
+const HOT_RANGES : usize = 10;
 
-fn decode (i : Num) -> Instruction {
-    let mut i = i;
-    
-    let opcode = i % 100;
-    
-    i /= 100;
-    
-    let mode1 = i % 10;
-    
-    i /= 10;
-    
-    let mode2 = i % 10;
-    
-    i /= 10;
-    
-    let mode3 = i % 10;
-    
-    return Instruction {
-        opcode,
-        mode1,
-        mode2, 
-        mode3
-    }
+struct Options {
+    limits : Limits,
+    profile : bool,
+    folded : Option<String>,
+    coverage : Option<String>,
+    compiled : bool,
 }
 
-fn safe_get(memory : &mut Vec<Num>, position : usize) -> Num {
-    if position >= memory.len() {
-        memory.resize(position + 1, 0);
-    }
-    memory[position]
+// Everything watching the run, only the enabled ones are traced
+struct Tracers {
+    profiler : Option<Profiler>,
+    coverage : Option<Coverage>,
 }
 
-fn safe_set(memory : &mut Vec<Num>, position : usize, value : Num) {
-    if position >= memory.len() {
-        memory.resize(position + 1, 0);
+impl Trace for Tracers {
+    fn instruction(&mut self, machine : &Machine, instruction : Instruction) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.instruction(machine, instruction);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.instruction(machine, instruction);
+        }
     }
-    memory[position] = value;
-}
 
-fn get_value(memory : &mut Vec<Num>, 
-             position : usize, 
-             mode : Num,
-             relative_base: Num) -> Num {
-
-    let memory_value = safe_get(memory, position);
-    if mode == 0 { // POSITION MODE
-        safe_get(memory, memory_value as usize)
-    } else if mode == 1 { // IMMEDIATE MODE
-        memory_value as Num
-    } else { // RELATIVE MODE
-        safe_get(memory, (memory_value + relative_base) as usize)
+    fn needs_input(&mut self, machine : &Machine) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.needs_input(machine);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.needs_input(machine);
+        }
     }
 }
 
-fn store_value(memory : &mut Vec<Num>, 
-               position : usize, 
-               value : Num, 
-               mode : Num,
-               relative_base : Num) {
-    let memory_value = safe_get(memory, position);
-    if mode == 0 { // POSITION MODE
-        safe_set(memory, memory_value as usize, value);
-    } else if mode == 2 {
-        safe_set(memory, (memory_value + relative_base) as usize, value);
-    } else {
-        panic!("Unexpected mode for a store operation");
+fn read_input(filename : &str) -> Vec<Num> {
+    match loader::load(&Source::from_arg(filename)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", filename, e);
+            process::exit(1);
+        }
     }
 }
 
-fn fetch_operands_and_store_result(memory : &mut Vec<Num>, 
-                                   pc : usize, 
-                                   instruction : Instruction,
-                                   relative_base : Num) -> Option<usize> {
-    
-    let op1 = get_value(memory, pc+1, instruction.mode1, relative_base);
-    let op2 = get_value(memory, pc+2, instruction.mode2, relative_base);
-    
-    let result : Num = match instruction.opcode {
-        1 => op1 + op2,
-        2 => op1 * op2,
-        _ => panic!("Unexpected opcode")
-    };
-
-    store_value(memory, pc+3, result, instruction.mode3, relative_base);
-
-    Some(pc + 4)
-}
-
-fn input(memory : &mut Vec<Num>, pc : usize, instruction : Instruction, relative_base : Num) -> Option<usize> {
+fn read_value() -> Num {
     let mut buffer = String::new();
-    
+
     println!("Please input a value");
     io::stdin().read_line(&mut buffer).unwrap();
-    
-    let result = Num::from_str_radix(&buffer.trim(), 10).unwrap();
-    store_value(memory, pc+1, result, instruction.mode1, relative_base);
 
-    Some(pc + 2)
+    buffer.trim().parse::<Num>().unwrap()
 }
 
-fn output(memory : &mut Vec<Num>, 
-          pc : usize, 
-          instruction : Instruction,
-          relative_base : Num) -> Option<usize> {
-    let value = get_value(memory, pc+1, instruction.mode1, relative_base);
+fn usage() -> ! {
+    eprintln!("Usage: day9 [--max-instructions N] [--timeout SECONDS] [--profile] [--folded FILE]");
+    eprintln!("            [--coverage FILE] [--compiled]");
+    eprintln!("  --profile   print execution counts, the hottest code and writes into code to stderr");
+    eprintln!("  --folded    write folded call stacks for flamegraph tools to FILE");
+    eprintln!("  --coverage  add this run's coverage to FILE, see intcode-coverage");
+    eprintln!("  --compiled  run the input.txt compiled into the binary, without limits or reports");
+    process::exit(1);
+}
 
-    println!("Output value {:?}", value);
+fn parse_options() -> Options {
+    let mut options = Options { limits : Limits::none(), profile : false, folded : None, coverage : None, compiled : false };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-instructions" => {
+                let value = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage());
+                options.limits = options.limits.instructions(value);
+            },
+            "--timeout" => match args.next().and_then(|value| value.parse::<f64>().ok()) {
+                Some(seconds) if seconds >= 0.0 => options.limits = options.limits.timeout(Duration::from_secs_f64(seconds)),
+                _ => usage(),
+            },
+            "--profile" => options.profile = true,
+            "--folded" => options.folded = Some(args.next().unwrap_or_else(|| usage())),
+            "--coverage" => options.coverage = Some(args.next().unwrap_or_else(|| usage())),
+            "--compiled" => options.compiled = true,
+            _ => usage(),
+        }
+    }
 
-    Some(pc + 2)
+    options
 }
 
-fn jump_if(memory : &mut Vec<Num>, 
-           pc : usize, 
-           instruction : Instruction,
-           relative_base : Num) -> Option<usize> {
-    let op1 = get_value(memory, pc+1, instruction.mode1, relative_base);
-
-    let result = match instruction.opcode {
-        5 => op1 != 0,
-        6 => op1 == 0,
-        _ => panic!("Unexpected opcode"),
-    };
+fn write_reports(options : &Options, tracers : &Tracers, machine : &Machine, program : &[Num]) {
+    if let Some(profiler) = &tracers.profiler {
+        if options.profile {
+            eprint!("{}", profiler.report(program, HOT_RANGES));
 
-    let op2 = get_value(memory, pc+2, instruction.mode2, relative_base);
+            let writes = machine.code_writes();
+            if !writes.is_empty() {
+                eprintln!("\nWrites into executed code:");
+            }
+            for write in writes {
+                eprintln!("  {} wrote to {} {} times", write.pc, write.address, write.count);
+            }
+        }
+        if let Some(filename) = &options.folded {
+            if let Err(e) = fs::write(filename, profiler.folded()) {
+                eprintln!("Could not write {}: {}", filename, e);
+            }
+        }
+    }
 
-    if result {
-        Some(op2 as usize)
-    } else {
-        Some(pc+3)
+    if let (Some(coverage), Some(filename)) = (&tracers.coverage, &options.coverage) {
+        // Coverage accumulates over runs, merge with what the file had. A
+        // file that can't be read or parsed is left alone rather than lost.
+        let previous = match fs::read_to_string(filename) {
+            Ok(text) => Coverage::parse(&text).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Coverage::new()),
+            Err(e) => Err(e.to_string()),
+        };
+        let mut total = previous.unwrap_or_else(|e| {
+            eprintln!("Could not read the coverage in {}, not writing it: {}", filename, e);
+            process::exit(1);
+        });
+        total.merge(coverage);
+        if let Err(e) = fs::write(filename, total.save()) {
+            eprintln!("Could not write {}: {}", filename, e);
+        }
     }
 }
 
-fn comparison(memory : &mut Vec<Num>, 
-              pc : usize, 
-              instruction : Instruction,
-              relative_base : Num) -> Option<usize> {
-    let op1 = get_value(memory, pc+1, instruction.mode1, relative_base);
-    let op2 = get_value(memory, pc+2, instruction.mode2, relative_base);
-
-    let result = match instruction.opcode {
-        7 => op1 < op2,
-        8 => op1 == op2,
-        _ => panic!("Unexpected opcode"),
-    };
-
-    let value = if result { 1 } else { 0 };
+fn run_compiled() {
+    let mut program = boost::Program::new();
 
-    store_value(memory, pc+3, value, instruction.mode3, relative_base);
+    loop {
+        let status = program.run();
 
-    Some(pc+4)
-}
-
-fn change_relative_base(memory : &mut Vec<Num>,
-                        pc : usize,
-                        instruction : Instruction,
-                        relative_base : &mut Num) -> Option<usize> {
-    let op1 = get_value(memory, pc+1, instruction.mode1, *relative_base);
-    *relative_base = (*relative_base) + op1; 
-    Some(pc+2)                        
-}
+        for value in program.take_outputs() {
+            println!("Output value {:?}", value);
+        }
 
-fn read_input(filename : &str) -> Vec<Num> {
-    let mut contents = String::new();
-
-    File::open(filename)
-        .unwrap()
-        .read_to_string(&mut contents)
-        .unwrap();
-
-    contents.split(',')
-            .map(|n| Num::from_str_radix(&n.trim(), 10)
-                     .unwrap()
-                )
-            .collect()    
+        match status {
+            Ok(Status::NeedsInput) => program.push_input(read_value()),
+            Ok(_) => {
+                println!("Halt!");
+                return;
+            },
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
 }
 
 fn main() {
-    let mut memory = read_input("input.txt");
+    let options = parse_options();
+    let limits = options.limits;
+    let memory = read_input("input.txt");
 
     println!("Welcome to the INTCODE computer!");
-    
-    let mut pc = 0;
-    let mut relative_base = 0;
-    
-    while pc < memory.len() {
-        let instruction = decode(memory[pc]);
-        let new_pc = match instruction.opcode {
-            99 => None,
-            1 | 2 => fetch_operands_and_store_result(&mut memory, 
-                pc, instruction, relative_base),
-            3 => input(&mut memory, pc, instruction, relative_base),
-            4 => output(&mut memory, pc, instruction, relative_base),
-            5 | 6 => jump_if(&mut memory, pc, instruction, relative_base),
-            7 | 8 => comparison(&mut memory, pc, instruction, 
-                relative_base),
-            9 => change_relative_base(&mut memory, pc, instruction, 
-                &mut relative_base),
-            _ => panic!("Unexpected opcode"),
+
+    if options.compiled {
+        run_compiled();
+        return;
+    }
+
+    let mut machine = Machine::new(memory.clone()).decode_cache(true);
+    let mut tracers = Tracers {
+        profiler : if options.profile || options.folded.is_some() { Some(Profiler::new()) } else { None },
+        coverage : options.coverage.as_ref().map(|_| Coverage::new()),
+    };
+    let tracing = tracers.profiler.is_some() || tracers.coverage.is_some();
+
+    loop {
+        // The instruction budget is for the whole run, not for each input
+        let remaining = Limits {
+            instructions : limits.instructions.map(|max| max.saturating_sub(machine.executed())),
+            ..limits
+        };
+        let status = if tracing {
+            machine.run_traced(&remaining, &mut tracers)
+        } else {
+            machine.run_with(&remaining)
         };
-        match new_pc {
-            Some(new_pc) => {pc = new_pc;}
-            None => {
-                println!("Halt!"); 
+
+        for value in machine.take_outputs() {
+            println!("Output value {:?}", value);
+        }
+
+        match status {
+            Ok(Status::NeedsInput) => machine.push_input(read_value()),
+            Ok(Status::Halted) => {
+                println!("Halt!");
+                write_reports(&options, &tracers, &machine, &memory);
                 return;
+            },
+            Ok(Status::BudgetExceeded) => {
+                eprintln!("Stopped at {} after {} instructions, the budget was exceeded",
+                    machine.pc(), machine.executed());
+                write_reports(&options, &tracers, &machine, &memory);
+                process::exit(2);
+            },
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                write_reports(&options, &tracers, &machine, &memory);
+                process::exit(1);
             }
         }
     }
@@ -0,0 +1,71 @@
+// Compares the machine with the threaded engine on real programs and
+// reports instructions per second. Run with cargo bench, there is no test
+// harness so this needs nothing but std.
+
+use intcode::Num;
+use intcode::bench::{self, Measurement};
+use intcode::loader;
+use intcode::machine::{Computer, Machine, Status};
+use intcode::threaded::Threaded;
+
+const BOOST : &str = include_str!("../../day9/input.txt");
+const GAME : &str = include_str!("../../day13/input.txt");
+
+const ITERATIONS : usize = 20;
+
+// BOOST in sensor boost mode
+fn boost(engine : &mut dyn Computer) -> Num {
+    engine.push_input(2);
+    assert_eq!(engine.run(), Ok(Status::Halted));
+    engine.take_outputs()[0]
+}
+
+// Plays the whole game, moving the paddle under the ball, and
+// returns the final score
+fn game(engine : &mut dyn Computer) -> Num {
+    let (mut ball, mut paddle, mut score) = (0, 0, 0);
+
+    loop {
+        let status = engine.run().unwrap();
+        for tile in engine.take_outputs().chunks(3) {
+            match tile {
+                [-1, 0, value] => score = *value,
+                [x, _, 3] => paddle = *x,
+                [x, _, 4] => ball = *x,
+                _ => {},
+            }
+        }
+        if status == Status::Halted {
+            return score;
+        }
+        engine.push_input((ball - paddle).signum());
+    }
+}
+
+fn compare(name : &str, program : &[Num], run : fn(&mut dyn Computer) -> Num) -> Vec<Measurement> {
+    // Both engines execute the same instructions
+    let mut counter = Machine::new(program.to_vec());
+    let expected = run(&mut counter);
+    let instructions = counter.executed();
+
+    let machine = bench::measure(&format!("{}/machine", name), ITERATIONS, || {
+        assert_eq!(run(&mut Machine::new(program.to_vec())), expected);
+    });
+    let threaded = bench::measure(&format!("{}/threaded", name), ITERATIONS, || {
+        assert_eq!(run(&mut Threaded::new(program.to_vec())), expected);
+    });
+    println!("{}: threaded is {:.2}x faster", name, machine.median().as_secs_f64() / threaded.median().as_secs_f64());
+
+    vec![machine.per_iteration(instructions, "instructions"), threaded.per_iteration(instructions, "instructions")]
+}
+
+fn main() {
+    let mut measurements = compare("day9/boost", &loader::parse(BOOST).unwrap(), boost);
+
+    // Free play
+    let mut game_program = loader::parse(GAME).unwrap();
+    game_program[0] = 2;
+    measurements.extend(compare("day13/game", &game_program, game));
+
+    bench::report(&measurements);
+}
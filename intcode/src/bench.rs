@@ -0,0 +1,102 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::hint::black_box;
+use std::io::{self, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Where record appends results: bench_output.txt at the top of the
+// repository, as seen from the crate being benchmarked, unless BENCH_OUTPUT
+// names another file
+pub const OUTPUT : &str = "../bench_output.txt";
+
+// The timings of one benchmark, fastest first
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name : String,
+    pub times : Vec<Duration>,
+    // How much work one iteration does, like instructions executed
+    pub work : Option<(u64, &'static str)>,
+}
+
+impl Measurement {
+    // Says how much work each iteration does, so a rate can be reported
+    pub fn per_iteration(self, amount : u64, unit : &'static str) -> Measurement {
+        Measurement { work : Some((amount, unit)), ..self }
+    }
+
+    pub fn median(&self) -> Duration {
+        self.times[self.times.len() / 2]
+    }
+
+    pub fn min(&self) -> Duration {
+        self.times[0]
+    }
+
+    pub fn max(&self) -> Duration {
+        self.times[self.times.len() - 1]
+    }
+
+    // Work done per second at the median time
+    pub fn rate(&self) -> Option<f64> {
+        self.work.map(|(amount, _)| amount as f64 / self.median().as_secs_f64())
+    }
+
+    pub fn summary(&self) -> String {
+        let mut line = format!("{:<28} median {:>11.3?}  min {:>11.3?}  max {:>11.3?}",
+            self.name, self.median(), self.min(), self.max());
+        if let (Some(rate), Some((_, unit))) = (self.rate(), self.work) {
+            line.push_str(&format!("  {:.1}M {}/s", rate / 1e6, unit));
+        }
+        line
+    }
+
+    // One line of JSON, times in nanoseconds
+    pub fn to_json(&self, timestamp : u64) -> String {
+        let mut json = format!("{{\"timestamp\": {}, \"benchmark\": \"{}\", \"iterations\": {}, \
+                                \"median_ns\": {}, \"min_ns\": {}, \"max_ns\": {}",
+            timestamp, self.name, self.times.len(),
+            self.median().as_nanos(), self.min().as_nanos(), self.max().as_nanos());
+        if let (Some(rate), Some((amount, unit))) = (self.rate(), self.work) {
+            json.push_str(&format!(", \"{}\": {}, \"{}_per_second\": {:.0}", unit, amount, unit, rate));
+        }
+        json.push('}');
+        json
+    }
+}
+
+// Times run over some iterations, after one run to warm up
+pub fn measure<T>(name : &str, iterations : usize, mut run : impl FnMut() -> T) -> Measurement {
+    black_box(run());
+
+    let mut times : Vec<Duration> = (0..iterations.max(1)).map(|_| {
+        let start = Instant::now();
+        black_box(run());
+        start.elapsed()
+    }).collect();
+    times.sort();
+
+    Measurement { name : name.to_string(), times, work : None }
+}
+
+// Appends the measurements to OUTPUT as JSON lines, so runs can be compared
+// over time
+pub fn record(measurements : &[Measurement]) -> io::Result<()> {
+    let path = env::var("BENCH_OUTPUT").unwrap_or_else(|_| OUTPUT.to_string());
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    for measurement in measurements {
+        writeln!(file, "{}", measurement.to_json(timestamp))?;
+    }
+    Ok(())
+}
+
+// Prints the measurements and records them, what every bench main ends with
+pub fn report(measurements : &[Measurement]) {
+    for measurement in measurements {
+        println!("{}", measurement.summary());
+    }
+    if let Err(e) = record(measurements) {
+        eprintln!("Could not record the results: {}", e);
+    }
+}
@@ -0,0 +1,45 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::cfg::Cfg;
+use intcode::loader::{self, Source};
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-cfg [--dot] PROGRAM");
+    eprintln!("Recovers the control flow graph of PROGRAM (\"-\" for stdin) and prints a");
+    eprintln!("summary, or the graph in Graphviz format with --dot.");
+    process::exit(1);
+}
+
+fn main() {
+    let mut dot = false;
+    let mut filename = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let filename = filename.unwrap_or_else(|| usage());
+    let program : Vec<Num> = loader::load(&Source::from_arg(&filename)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", filename, e);
+        process::exit(1);
+    });
+
+    let cfg = Cfg::build(&program);
+
+    if dot {
+        print!("{}", cfg.to_dot(&program));
+        return;
+    }
+
+    println!("{} blocks, {} edges", cfg.blocks.len(), cfg.edges());
+    println!("Indirect jumps at {:?}", cfg.indirect_jumps());
+    for write in &cfg.self_modifying {
+        println!("Write into code at {} from {}", write.address, write.pc);
+    }
+}
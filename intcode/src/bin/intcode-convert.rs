@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use intcode::Num;
+use intcode::binary;
+use intcode::loader::{self, Source};
+
+enum Format {
+    Text,
+    Binary,
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-convert [--to text|binary] INPUT OUTPUT");
+    eprintln!("Converts between text and binary programs, \"-\" is stdin or stdout.");
+    eprintln!("Without --to, the output uses the format the input is not in.");
+    process::exit(1);
+}
+
+fn main() {
+    let mut format = None;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => format = match args.next().as_deref() {
+                Some("text") => Some(Format::Text),
+                Some("binary") => Some(Format::Binary),
+                _ => usage(),
+            },
+            _ => files.push(arg),
+        }
+    }
+
+    if files.len() != 2 {
+        usage();
+    }
+
+    let source = Source::from_arg(&files[0]);
+    let bytes = source.read().unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", files[0], e);
+        process::exit(1);
+    });
+    let format = format.unwrap_or(if binary::is_binary(&bytes) { Format::Text } else { Format::Binary });
+
+    let program : Vec<Num> = match loader::load_bytes(bytes) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", files[0], e);
+            process::exit(1);
+        }
+    };
+
+    let output = match format {
+        Format::Text => loader::format_text(&program).into_bytes(),
+        Format::Binary => binary::encode(&program),
+    };
+
+    let result = if files[1] == "-" {
+        io::stdout().write_all(&output)
+    } else {
+        fs::write(&files[1], &output)
+    };
+
+    if let Err(e) = result {
+        eprintln!("Could not write {}: {}", files[1], e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::Num;
+use intcode::coverage::Coverage;
+use intcode::loader;
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-coverage [--merge OUTPUT] PROGRAM COVERAGE...");
+    eprintln!("Prints the program annotated with the merged coverage of every run:");
+    eprintln!("  + executed  - never executed  d accessed as data");
+    eprintln!("With --merge, the merged coverage is also written to OUTPUT.");
+    process::exit(1);
+}
+
+fn main() {
+    let mut merge = None;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--merge" => merge = Some(args.next().unwrap_or_else(|| usage())),
+            _ => files.push(arg),
+        }
+    }
+
+    if files.len() < 2 {
+        usage();
+    }
+
+    let program : Vec<Num> = loader::load_file(&files[0]).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", files[0], e);
+        process::exit(1);
+    });
+
+    let mut coverage = Coverage::new();
+    for filename in &files[1..] {
+        let run = fs::read_to_string(filename).map_err(|e| e.to_string())
+            .and_then(|text| Coverage::parse(&text).map_err(|e| e.to_string()));
+        match run {
+            Ok(run) => coverage.merge(&run),
+            Err(e) => {
+                eprintln!("Could not read {}: {}", filename, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    print!("{}", coverage.listing(&program));
+
+    let ((executed, instructions), (covered, branches)) = coverage.summary(&program);
+    println!();
+    println!("{} of {} instructions executed, {} of {} branches taken both ways",
+        executed, instructions, covered, branches);
+
+    if let Some(filename) = merge {
+        if let Err(e) = fs::write(&filename, coverage.save()) {
+            eprintln!("Could not write {}: {}", filename, e);
+            process::exit(1);
+        }
+    }
+}
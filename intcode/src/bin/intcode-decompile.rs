@@ -0,0 +1,22 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::decompile;
+use intcode::loader::{self, Source};
+
+fn main() {
+    let args : Vec<String> = env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("Usage: intcode-decompile PROGRAM");
+        eprintln!("Prints PROGRAM (\"-\" for stdin) as structured pseudo code.");
+        process::exit(1);
+    }
+
+    let program : Vec<Num> = loader::load(&Source::from_arg(&args[0])).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", args[0], e);
+        process::exit(1);
+    });
+
+    print!("{}", decompile::decompile(&program));
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::diff::{self, Snapshot};
+use intcode::disasm;
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-diff [--ranges] OLD NEW");
+    eprintln!("Compares two machine snapshots and lists the changed addresses, grouped");
+    eprintln!("into ranges of consecutive addresses with the instructions that last wrote");
+    eprintln!("them. With --ranges only the ranges are listed, not every address.");
+    process::exit(1);
+}
+
+fn load(filename : &str) -> Snapshot {
+    let snapshot = fs::read_to_string(filename).map_err(|e| e.to_string())
+        .and_then(|text| Snapshot::parse(&text).map_err(|e| e.to_string()));
+    snapshot.unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        process::exit(1);
+    })
+}
+
+fn main() {
+    let mut ranges_only = false;
+    let mut files = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--ranges" => ranges_only = true,
+            _ => files.push(arg),
+        }
+    }
+
+    if files.len() != 2 {
+        usage();
+    }
+
+    let (old, new) = (load(&files[0]), load(&files[1]));
+    let diff = diff::diff(&old, &new);
+
+    println!("pc {} -> {}, relative base {} -> {}", diff.pc.0, diff.pc.1, diff.relative_base.0, diff.relative_base.1);
+    if diff.is_empty() {
+        println!("Memory is the same");
+        return;
+    }
+
+    let ranges = diff.ranges();
+    println!("{} addresses changed in {} ranges", diff.changes.len(), ranges.len());
+
+    for range in &ranges {
+        let writers : Vec<String> = range.writers().iter().map(|pc| pc.to_string()).collect();
+        let written = if writers.is_empty() { "unknown".to_string() } else { writers.join(", ") };
+        println!();
+        if range.start == range.end {
+            println!("{}  written by {}", range.start, written);
+        } else {
+            println!("{}..={}  written by {}", range.start, range.end, written);
+        }
+
+        if ranges_only {
+            continue;
+        }
+        for change in &range.changes {
+            println!("  {:>6}  {:>12} -> {}", change.address, change.old, change.new);
+        }
+    }
+
+    if !diff.writers().is_empty() {
+        println!();
+        println!("Writers:");
+        for pc in diff.writers() {
+            println!("  {:>6}  {}", pc, disasm::instruction_at(&new.memory, pc).0);
+        }
+    }
+}
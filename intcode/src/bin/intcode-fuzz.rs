@@ -0,0 +1,62 @@
+use std::env;
+use std::process;
+
+use intcode::fuzz::{self, CachedVm, Config, Feed, Implementation, ModeRule, Reference, ThreadedVm, Vm};
+use intcode::loader;
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-fuzz [--against feeds|cache|threaded|day5-model|day9-model] [--seed N] [--cases N]");
+    eprintln!("                    [--instructions N] [--no-relative] [--self-modifying]");
+    eprintln!("Runs random programs on the machine and another implementation and");
+    eprintln!("prints the smallest program they disagree on.");
+    process::exit(1);
+}
+
+fn number<T : std::str::FromStr>(arg : Option<String>) -> T {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn main() {
+    let mut config = Config::default();
+    let mut against = String::from("day9-model");
+    let mut seed = 0;
+    let mut cases = 10000;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--against" => against = args.next().unwrap_or_else(|| usage()),
+            "--seed" => seed = number(args.next()),
+            "--cases" => cases = number(args.next()),
+            "--instructions" => config.instructions = number(args.next()),
+            "--no-relative" => config.relative = false,
+            "--self-modifying" => config.self_modifying = true,
+            _ => usage(),
+        }
+    }
+
+    let other : Box<dyn Implementation> = match against.as_str() {
+        "feeds" => Box::new(Vm(Feed::OnDemand)),
+        "cache" => Box::new(CachedVm),
+        "threaded" => Box::new(ThreadedVm),
+        "day5-model" => Box::new(Reference(ModeRule::Day5)),
+        "day9-model" => Box::new(Reference(ModeRule::Day9)),
+        _ => usage(),
+    };
+    let machine = Vm(Feed::Upfront);
+    let implementations = [&machine as &dyn Implementation, other.as_ref()];
+
+    match fuzz::differential(&implementations, &config, seed, cases) {
+        None => println!("No disagreement in {} cases", cases),
+        Some(counterexample) => {
+            println!("Disagreement on seed {}, shrunk from {} to {} words", counterexample.seed,
+                counterexample.original.program.len(), counterexample.shrunk.program.len());
+            println!("Program: {}", loader::format_text(&counterexample.shrunk.program).trim_end());
+            println!("Inputs: {:?}", counterexample.shrunk.inputs);
+            for (name, outcome) in &counterexample.outcomes {
+                println!("  {}: {}", name, outcome);
+            }
+            process::exit(1);
+        }
+    }
+}
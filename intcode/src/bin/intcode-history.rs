@@ -0,0 +1,70 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::disasm;
+use intcode::loader::{self, Source};
+use intcode::machine::Machine;
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-history [--history N] [--input VALUE]... PROGRAM ADDRESS");
+    eprintln!("Runs PROGRAM (\"-\" for stdin) until it stops, then steps backwards through");
+    eprintln!("the last N instructions listing every write to ADDRESS, latest first.");
+    process::exit(1);
+}
+
+fn number<T : std::str::FromStr>(arg : Option<String>) -> T {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn main() {
+    let mut history = 1_000_000;
+    let mut inputs : Vec<Num> = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--history" => history = number(args.next()),
+            "--input" => inputs.push(number(args.next())),
+            _ => positional.push(arg),
+        }
+    }
+
+    if positional.len() != 2 {
+        usage();
+    }
+    let address : usize = number(positional.pop());
+    let filename = &positional[0];
+
+    let program : Vec<Num> = loader::load(&Source::from_arg(filename)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", filename, e);
+        process::exit(1);
+    });
+
+    let mut machine = Machine::new(program).history_length(history);
+    for input in inputs {
+        machine.push_input(input);
+    }
+
+    match machine.run() {
+        Ok(status) => println!("{:?} at {} after {} instructions", status, machine.pc(), machine.executed()),
+        Err(e) => println!("Failed with {} after {} instructions", e, machine.executed()),
+    }
+    println!("Address {} holds {}", address, machine.peek(address));
+
+    let mut writes = 0;
+    while let Some(write) = machine.reverse_continue(address) {
+        let (text, _) = disasm::instruction_at(machine.memory(), write.pc);
+        println!("  step {:>8}  pc {:>5}  {:<28} {} -> {}", write.step, write.pc, text, write.old, write.new);
+        writes += 1;
+    }
+
+    if writes == 0 {
+        println!("No writes to {} in the last {} instructions", address, history);
+    }
+    let dropped = machine.history().map_or(0, |history| history.dropped());
+    if dropped > 0 {
+        println!("{} earlier instructions are past the history", dropped);
+    }
+}
@@ -0,0 +1,80 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::loader::{self, Source};
+use intcode::network::{Event, Network};
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-network [--machines N] [--rounds N] [--quiet] PROGRAM");
+    eprintln!("Boots N machines (50 by default) running PROGRAM (\"-\" for stdin) on a packet");
+    eprintln!("network with a NAT at address 255, and prints every packet routed until the");
+    eprintln!("NAT wakes address 0 with the same Y twice in a row.");
+    process::exit(1);
+}
+
+fn number<T : std::str::FromStr>(arg : Option<String>) -> T {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn main() {
+    let mut machines = 50;
+    let mut rounds = 100_000;
+    let mut quiet = false;
+    let mut filename = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--machines" => machines = number(args.next()),
+            "--rounds" => rounds = number(args.next()),
+            "--quiet" => quiet = true,
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let filename = filename.unwrap_or_else(|| usage());
+    let program : Vec<Num> = loader::load(&Source::from_arg(&filename)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", filename, e);
+        process::exit(1);
+    });
+
+    let mut network = Network::boot(&program, machines);
+    let mut first_nat = None;
+    let mut last_wake = None;
+
+    while network.rounds() < rounds && !network.is_halted() {
+        let events = network.step().unwrap_or_else(|e| {
+            eprintln!("Network failed: {}", e);
+            process::exit(1);
+        });
+
+        for event in events {
+            let round = network.rounds();
+            match event {
+                Event::Sent(p) if !quiet => println!("{:>6}  {:>3} -> {:<3}  {} {}", round, p.from, p.to, p.x, p.y),
+                Event::Sent(_) => {},
+                Event::ToNat(p) => {
+                    first_nat.get_or_insert(p.y);
+                    if !quiet {
+                        println!("{:>6}  {:>3} -> NAT  {} {}", round, p.from, p.x, p.y);
+                    }
+                },
+                Event::Dropped(p) => println!("{:>6}  {:>3} -> {:<3}  {} {} dropped, no such address", round, p.from, p.to, p.x, p.y),
+                Event::Wake(p) => {
+                    println!("{:>6}  NAT -> {:<3}  {} {} (idle)", round, p.to, p.x, p.y);
+                    if last_wake == Some(p.y) {
+                        println!("First Y sent to the NAT: {:?}", first_nat);
+                        println!("The NAT sent Y {} twice in a row", p.y);
+                        return;
+                    }
+                    last_wake = Some(p.y);
+                },
+            }
+        }
+    }
+
+    println!("First Y sent to the NAT: {:?}", first_nat);
+    println!("Stopped after {} rounds", network.rounds());
+}
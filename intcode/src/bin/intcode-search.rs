@@ -0,0 +1,71 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::loader::{self, Source};
+use intcode::machine::Limits;
+use intcode::patch::{self, Search, Watch};
+
+fn usage() -> ! {
+    eprintln!("Usage: intcode-search [--patch A=V,...] --vary A=START..=END,... --target VALUE");
+    eprintln!("                      [--output] [--input VALUE]... [--instructions N] PROGRAM");
+    eprintln!("Runs PROGRAM (\"-\" for stdin) with every combination of values at the varied");
+    eprintln!("addresses until address 0, or the last output with --output, equals VALUE.");
+    eprintln!("Ranges are inclusive with ..= and exclusive with .., like in Rust.");
+    process::exit(1);
+}
+
+fn number<T : std::str::FromStr>(arg : Option<String>) -> T {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn parsed<T, E : std::fmt::Display>(result : Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
+
+fn main() {
+    let mut patches = Vec::new();
+    let mut search = Search::new(Vec::new(), 0);
+    let mut target = None;
+    let mut filename = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--patch" => patches.extend(parsed(patch::parse(&args.next().unwrap_or_else(|| usage())))),
+            "--vary" => search.ranges.extend(parsed(patch::parse_ranges(&args.next().unwrap_or_else(|| usage())))),
+            "--target" => target = Some(number(args.next())),
+            "--output" => search.watch = Watch::Output,
+            "--input" => search.inputs.push(number(args.next())),
+            "--instructions" => search.limits = Limits::none().instructions(number(args.next())),
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let (filename, target) = match (filename, target) {
+        (Some(filename), Some(target)) if !search.ranges.is_empty() => (filename, target),
+        _ => usage(),
+    };
+    search.target = target;
+
+    let mut program : Vec<Num> = loader::load(&Source::from_arg(&filename)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", filename, e);
+        process::exit(1);
+    });
+    parsed(patch::apply(&mut program, &patches));
+
+    match search.run(&program) {
+        (Some(found), runs) => {
+            let found : Vec<String> = found.iter().map(|patch| patch.to_string()).collect();
+            println!("Found {} after {} runs", found.join(","), runs);
+        },
+        (None, runs) => {
+            println!("No match after {} runs", runs);
+            process::exit(1);
+        },
+    }
+}
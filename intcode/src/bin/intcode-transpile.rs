@@ -0,0 +1,23 @@
+use std::env;
+use std::process;
+
+use intcode::Num;
+use intcode::loader::{self, Source};
+use intcode::transpile;
+
+fn main() {
+    let args : Vec<String> = env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("Usage: intcode-transpile PROGRAM");
+        eprintln!("Prints a Rust module running PROGRAM (\"-\" for stdin) as compiled code.");
+        eprintln!("Its Program type implements intcode::machine::Computer.");
+        process::exit(1);
+    }
+
+    let program : Vec<Num> = loader::load(&Source::from_arg(&args[0])).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", args[0], e);
+        process::exit(1);
+    });
+
+    print!("{}", transpile::transpile(&program));
+}
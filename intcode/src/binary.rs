@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::Num;
+
+// Compact program format:
+//
+//   magic     "ICB"
+//   version   1 byte
+//   length    number of words, as an unsigned LEB128 varint
+//   words     each word zigzag encoded, then as an unsigned LEB128 varint
+//   checksum  FNV-1a (32 bits, little endian) of everything before it
+pub const MAGIC : &[u8; 3] = b"ICB";
+pub const VERSION : u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated { offset : usize },
+    Overflow { offset : usize },
+    Checksum { expected : u32, found : u32 },
+    TrailingBytes { offset : usize },
+    OutOfRange { index : usize, value : Num },
+    // Well formed but without any words, which the text format rejects too
+    EmptyProgram,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryError::BadMagic => write!(f, "not a binary intcode program"),
+            BinaryError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            BinaryError::Truncated { offset } => write!(f, "unexpected end of data at byte {}", offset),
+            BinaryError::Overflow { offset } => write!(f, "varint too long at byte {}", offset),
+            BinaryError::Checksum { expected, found } =>
+                write!(f, "checksum mismatch, expected {:08x} but found {:08x}", expected, found),
+            BinaryError::TrailingBytes { offset } => write!(f, "unexpected data after the checksum at byte {}", offset),
+            BinaryError::OutOfRange { index, value } => write!(f, "word {} ({}) does not fit", index, value),
+            BinaryError::EmptyProgram => write!(f, "empty program"),
+        }
+    }
+}
+
+impl Error for BinaryError {}
+
+pub fn is_binary(bytes : &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn fnv1a(bytes : &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+fn zigzag(value : Num) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value : u64) -> Num {
+    ((value >> 1) as Num) ^ -((value & 1) as Num)
+}
+
+fn write_varint(out : &mut Vec<u8>, mut value : u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes : &[u8], offset : &mut usize) -> Result<u64, BinaryError> {
+    let start = *offset;
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let byte = *bytes.get(*offset).ok_or(BinaryError::Truncated { offset : *offset })?;
+        *offset += 1;
+
+        if shift == 63 && byte > 1 {
+            return Err(BinaryError::Overflow { offset : start });
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+pub fn encode(program : &[Num]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(program.len() * 2 + 16);
+
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    write_varint(&mut out, program.len() as u64);
+    for &word in program {
+        write_varint(&mut out, zigzag(word));
+    }
+
+    let checksum = fnv1a(&out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+pub fn decode(bytes : &[u8]) -> Result<Vec<Num>, BinaryError> {
+    if !is_binary(bytes) {
+        return Err(BinaryError::BadMagic);
+    }
+    let mut offset = MAGIC.len();
+
+    let version = *bytes.get(offset).ok_or(BinaryError::Truncated { offset })?;
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+    offset += 1;
+
+    let length = read_varint(bytes, &mut offset)? as usize;
+    // Every word takes at least a byte, don't trust the length any further
+    let mut program = Vec::with_capacity(length.min(bytes.len()));
+    for _ in 0..length {
+        program.push(unzigzag(read_varint(bytes, &mut offset)?));
+    }
+
+    let checksum = bytes.get(offset..offset + 4).ok_or(BinaryError::Truncated { offset })?;
+    let found = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    let expected = fnv1a(&bytes[..offset]);
+    if found != expected {
+        return Err(BinaryError::Checksum { expected, found });
+    }
+    offset += 4;
+
+    if offset != bytes.len() {
+        return Err(BinaryError::TrailingBytes { offset });
+    }
+    if program.is_empty() {
+        return Err(BinaryError::EmptyProgram);
+    }
+
+    Ok(program)
+}
@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::Num;
+use crate::disasm;
+use crate::machine::{decode, Instruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    // A jump with an immediate target
+    Taken,
+    // Straight on to the next instruction
+    FallThrough,
+    // Back from a call: the caller stored this address before jumping away
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub to : usize,
+    pub kind : EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start : usize,
+    // One past the last word of the last instruction
+    pub end : usize,
+    pub instructions : Vec<usize>,
+    pub edges : Vec<Edge>,
+    // Ends with a jump whose target is only known at run time
+    pub indirect : bool,
+    // Ends with a word that isn't a valid instruction
+    pub invalid : bool,
+}
+
+// A write whose address is fixed and lands on code the analysis found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    pub pc : usize,
+    pub address : usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks : BTreeMap<usize, Block>,
+    pub self_modifying : Vec<SelfModification>,
+}
+
+// What the analysis knows about a single instruction
+#[derive(Debug, Clone)]
+struct Node {
+    instruction : Instruction,
+    length : usize,
+    successors : Vec<Edge>,
+    indirect : bool,
+    invalid : bool,
+}
+
+fn word(memory : &[Num], position : usize) -> Num {
+    memory.get(position).copied().unwrap_or(0)
+}
+
+fn analyse(memory : &[Num], pc : usize) -> Node {
+    let instruction = decode(word(memory, pc));
+    let length = 1 + disasm::parameters(instruction.opcode);
+    let valid = disasm::mnemonic(instruction.opcode).is_some()
+        && word(memory, pc) >= 0
+        && pc + length <= memory.len()
+        && [instruction.mode1, instruction.mode2, instruction.mode3].iter().all(|&mode| mode <= 2);
+
+    let mut node = Node { instruction, length, successors : Vec::new(), indirect : false, invalid : !valid };
+    if !valid {
+        return node;
+    }
+
+    match instruction.opcode {
+        99 => {},
+        5 | 6 => {
+            // With an immediate condition the jump always goes the same way
+            let (can_jump, can_continue) = if instruction.mode1 == 1 {
+                let jumps = (word(memory, pc + 1) != 0) == (instruction.opcode == 5);
+                (jumps, !jumps)
+            } else {
+                (true, true)
+            };
+
+            if can_jump {
+                let target = word(memory, pc + 2);
+                if instruction.mode2 == 1 && target >= 0 {
+                    node.successors.push(Edge { to : target as usize, kind : EdgeKind::Taken });
+                } else {
+                    node.indirect = true;
+                }
+            }
+            if can_continue {
+                node.successors.push(Edge { to : pc + length, kind : EdgeKind::FallThrough });
+            }
+        },
+        _ => node.successors.push(Edge { to : pc + length, kind : EdgeKind::FallThrough }),
+    }
+    node
+}
+
+// The value an add or multiply of two immediates stores
+fn stored_constant(memory : &[Num], pc : usize, node : &Node) -> Option<Num> {
+    let instruction = node.instruction;
+    if instruction.mode1 != 1 || instruction.mode2 != 1 {
+        return None;
+    }
+    let (a, b) = (word(memory, pc + 1), word(memory, pc + 2));
+    match instruction.opcode {
+        1 => Some(a.wrapping_add(b)),
+        2 => Some(a.wrapping_mul(b)),
+        _ => None,
+    }
+}
+
+impl Cfg {
+    // Recovers the code reachable from address 0. Indirect jumps can't be
+    // followed, but the usual calling convention is: store the return
+    // address, then jump unconditionally to the function. When the
+    // instruction before such a jump stores the address right after it, that
+    // address is followed as well.
+    pub fn build(memory : &[Num]) -> Cfg {
+        let mut nodes : BTreeMap<usize, Node> = BTreeMap::new();
+        let mut returns : BTreeMap<usize, usize> = BTreeMap::new();
+        let mut pending = vec![0];
+
+        while !pending.is_empty() {
+            while let Some(pc) = pending.pop() {
+                if pc >= memory.len() || nodes.contains_key(&pc) {
+                    continue;
+                }
+                let node = analyse(memory, pc);
+                pending.extend(node.successors.iter().map(|edge| edge.to));
+                nodes.insert(pc, node);
+            }
+
+            // Look for calls among what was found so far
+            let ends : BTreeMap<usize, usize> = nodes.iter().map(|(&pc, node)| (pc + node.length, pc)).collect();
+            for (&pc, node) in &nodes {
+                let unconditional = matches!(node.instruction.opcode, 5 | 6)
+                    && node.successors.len() == 1
+                    && node.successors[0].kind == EdgeKind::Taken;
+                if !unconditional || returns.contains_key(&pc) {
+                    continue;
+                }
+                let after = pc + node.length;
+                let stores_return = ends.get(&pc)
+                    .and_then(|&previous| stored_constant(memory, previous, &nodes[&previous]))
+                    .is_some_and(|value| value == after as Num);
+                if stores_return {
+                    returns.insert(pc, after);
+                    pending.push(after);
+                }
+            }
+        }
+
+        for (&pc, &after) in &returns {
+            if let Some(node) = nodes.get_mut(&pc) {
+                node.successors.push(Edge { to : after, kind : EdgeKind::Return });
+            }
+        }
+
+        // Blocks start at the entry, at jump and return targets and right
+        // after any jump
+        let mut leaders : BTreeSet<usize> = BTreeSet::new();
+        leaders.insert(0);
+        for (&pc, node) in &nodes {
+            if matches!(node.instruction.opcode, 5 | 6) || node.invalid {
+                leaders.extend(node.successors.iter().map(|edge| edge.to));
+                leaders.insert(pc + node.length);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &leader in &leaders {
+            if !nodes.contains_key(&leader) {
+                continue;
+            }
+            let mut block = Block {
+                start : leader,
+                end : leader,
+                instructions : Vec::new(),
+                edges : Vec::new(),
+                indirect : false,
+                invalid : false,
+            };
+            let mut pc = leader;
+            loop {
+                let node = &nodes[&pc];
+                block.instructions.push(pc);
+                block.end = pc + node.length;
+
+                let next = pc + node.length;
+                let continues = node.successors.len() == 1
+                    && node.successors[0].kind == EdgeKind::FallThrough
+                    && !matches!(node.instruction.opcode, 5 | 6);
+                if !continues || leaders.contains(&next) || !nodes.contains_key(&next) {
+                    block.edges = node.successors.clone();
+                    block.indirect = node.indirect;
+                    block.invalid = node.invalid;
+                    break;
+                }
+                pc = next;
+            }
+            blocks.insert(leader, block);
+        }
+
+        // Writes with a fixed address into any word the code uses, including
+        // words that only become valid instructions once written
+        let code : BTreeSet<usize> = nodes.iter()
+            .flat_map(|(&pc, node)| pc..pc + if node.invalid { 1 } else { node.length })
+            .collect();
+        let mut self_modifying = Vec::new();
+        for (&pc, node) in &nodes {
+            let instruction = node.instruction;
+            let write = match instruction.opcode {
+                1 | 2 | 7 | 8 => Some((pc + 3, instruction.mode3)),
+                3 => Some((pc + 1, instruction.mode1)),
+                _ => None,
+            };
+            if let Some((position, 0)) = write {
+                let address = word(memory, position);
+                if address >= 0 && code.contains(&(address as usize)) {
+                    self_modifying.push(SelfModification { pc, address : address as usize });
+                }
+            }
+        }
+
+        Cfg { blocks, self_modifying }
+    }
+
+    pub fn edges(&self) -> usize {
+        self.blocks.values().map(|block| block.edges.len()).sum()
+    }
+
+    pub fn indirect_jumps(&self) -> Vec<usize> {
+        self.blocks.values()
+            .filter(|block| block.indirect)
+            .map(|block| *block.instructions.last().unwrap())
+            .collect()
+    }
+
+    // Graphviz source: one node per block listing its instructions. Taken
+    // jumps are solid, fall throughs dashed and returns dotted. Blocks ending
+    // in an indirect jump are red, blocks that write into code are orange.
+    pub fn to_dot(&self, memory : &[Num]) -> String {
+        let mut out = String::new();
+        let writers : BTreeSet<usize> = self.self_modifying.iter().map(|write| write.pc).collect();
+
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=monospace];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for &pc in &block.instructions {
+                let text = disasm::instruction_at(memory, pc).0;
+                write!(label, "{}: {}\\l", pc, text.replace('"', "\\\"")).unwrap();
+            }
+
+            let mut attributes = String::new();
+            if block.indirect || block.invalid {
+                attributes.push_str(", color=red");
+            }
+            if block.instructions.iter().any(|pc| writers.contains(pc)) {
+                attributes.push_str(", style=filled, fillcolor=orange");
+            }
+            writeln!(out, "    b{} [label=\"{}\"{}];", block.start, label, attributes).unwrap();
+
+            for edge in &block.edges {
+                let style = match edge.kind {
+                    EdgeKind::Taken => "solid",
+                    EdgeKind::FallThrough => "dashed",
+                    EdgeKind::Return => "dotted",
+                };
+                writeln!(out, "    b{} -> b{} [style={}];", block.start, edge.to, style).unwrap();
+            }
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use crate::Num;
+use crate::disasm;
+use crate::machine::{decode, Instruction, Machine, Trace};
+
+// How often a conditional jump went each way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken : u64,
+    pub not_taken : u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub line : usize,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed coverage at line {}", self.line)
+    }
+}
+
+impl Error for FormatError {}
+
+// Which instructions executed, which words were accessed as data and which
+// way each conditional jump went. Coverage from several runs of the same
+// program can be merged, and saved to a file in between.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    executed : BTreeMap<usize, u64>,
+    data : BTreeSet<usize>,
+    branches : BTreeMap<usize, Branch>,
+}
+
+// How a word shows up in the annotated listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Executed,
+    Unexecuted,
+    Data,
+    Untouched,
+}
+
+impl Mark {
+    fn symbol(self) -> char {
+        match self {
+            Mark::Executed => '+',
+            Mark::Unexecuted => '-',
+            Mark::Data => 'd',
+            Mark::Untouched => ' ',
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn hits(&self, pc : usize) -> u64 {
+        self.executed.get(&pc).copied().unwrap_or(0)
+    }
+
+    pub fn is_data(&self, address : usize) -> bool {
+        self.data.contains(&address)
+    }
+
+    pub fn branch(&self, pc : usize) -> Option<Branch> {
+        self.branches.get(&pc).copied()
+    }
+
+    pub fn merge(&mut self, other : &Coverage) {
+        for (&pc, &hits) in &other.executed {
+            *self.executed.entry(pc).or_insert(0) += hits;
+        }
+        self.data.extend(other.data.iter().copied());
+        for (&pc, branch) in &other.branches {
+            let entry = self.branches.entry(pc).or_default();
+            entry.taken += branch.taken;
+            entry.not_taken += branch.not_taken;
+        }
+    }
+
+    // Splits memory into listing lines as (address, length, mark). Executed
+    // instructions come first, then words used as data, and whatever is left
+    // is an unexecuted instruction if it decodes as one.
+    pub fn classify(&self, memory : &[Num]) -> Vec<(usize, usize, Mark)> {
+        let mut lines = Vec::new();
+        let mut pc = 0;
+
+        while pc < memory.len() {
+            let decoded = disasm::length_at(memory, pc);
+            let line = if self.executed.contains_key(&pc) {
+                (pc, decoded.unwrap_or(1), Mark::Executed)
+            } else if self.is_data(pc) {
+                (pc, 1, Mark::Data)
+            } else if let Some(length) = decoded.filter(|&length| {
+                (pc..pc + length).all(|address| !self.is_data(address) && !self.executed.contains_key(&address))
+            }) {
+                (pc, length, Mark::Unexecuted)
+            } else {
+                (pc, 1, Mark::Untouched)
+            };
+            lines.push(line);
+            pc += line.1;
+        }
+        lines
+    }
+
+    // The program with every line marked: + executed, - never executed,
+    // d accessed as data, blank for words nothing touched. Conditional jumps
+    // also show how often they went each way, with ! when one side never ran.
+    pub fn listing(&self, memory : &[Num]) -> String {
+        let mut out = String::new();
+
+        for (pc, _, mark) in self.classify(memory) {
+            let text = match mark {
+                Mark::Executed | Mark::Unexecuted => disasm::instruction_at(memory, pc).0,
+                _ => format!("data {}", memory[pc]),
+            };
+            write!(out, "{} {:>6} {:>10}  {}", mark.symbol(), pc,
+                if mark == Mark::Executed { self.hits(pc).to_string() } else { String::new() }, text).unwrap();
+
+            if let Some(branch) = self.branch(pc) {
+                let partial = if branch.taken == 0 || branch.not_taken == 0 { " !" } else { "" };
+                write!(out, "  (taken {}, not taken {}){}", branch.taken, branch.not_taken, partial).unwrap();
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    // Executed and total instructions, then fully covered and total branches
+    pub fn summary(&self, memory : &[Num]) -> ((usize, usize), (usize, usize)) {
+        let lines = self.classify(memory);
+        let executed = lines.iter().filter(|line| line.2 == Mark::Executed).count();
+        let instructions = executed + lines.iter().filter(|line| line.2 == Mark::Unexecuted).count();
+
+        let branches = lines.iter()
+            .filter(|line| line.2 != Mark::Data && line.2 != Mark::Untouched)
+            .map(|line| decode(memory[line.0]))
+            .filter(|instruction| matches!(instruction.opcode, 5 | 6) && instruction.mode1 != 1)
+            .count();
+        let covered = self.branches.values().filter(|branch| branch.taken > 0 && branch.not_taken > 0).count();
+
+        ((executed, instructions), (covered, branches))
+    }
+
+    // One record per line: "exec PC HITS", "data ADDRESS" or
+    // "branch PC TAKEN NOT_TAKEN"
+    pub fn save(&self) -> String {
+        let mut out = String::new();
+        for (pc, hits) in &self.executed {
+            writeln!(out, "exec {} {}", pc, hits).unwrap();
+        }
+        for address in &self.data {
+            writeln!(out, "data {}", address).unwrap();
+        }
+        for (pc, branch) in &self.branches {
+            writeln!(out, "branch {} {} {}", pc, branch.taken, branch.not_taken).unwrap();
+        }
+        out
+    }
+
+    pub fn parse(text : &str) -> Result<Coverage, FormatError> {
+        let mut coverage = Coverage::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let error = FormatError { line : i + 1 };
+            let fields : Vec<&str> = line.split_whitespace().collect();
+            let number = |index : usize| -> Result<u64, FormatError> {
+                fields.get(index).and_then(|field| field.parse().ok()).ok_or_else(|| error.clone())
+            };
+
+            match (fields.first(), fields.len()) {
+                (None, _) => {},
+                (Some(&"exec"), 3) => *coverage.executed.entry(number(1)? as usize).or_insert(0) += number(2)?,
+                (Some(&"data"), 2) => { coverage.data.insert(number(1)? as usize); },
+                (Some(&"branch"), 4) => {
+                    let branch = coverage.branches.entry(number(1)? as usize).or_default();
+                    branch.taken += number(2)?;
+                    branch.not_taken += number(3)?;
+                },
+                _ => return Err(error),
+            }
+        }
+
+        Ok(coverage)
+    }
+}
+
+impl Trace for Coverage {
+    fn instruction(&mut self, machine : &Machine, instruction : Instruction) {
+        let pc = machine.pc();
+        *self.executed.entry(pc).or_insert(0) += 1;
+
+        let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+        for (i, &mode) in modes.iter().enumerate().take(disasm::parameters(instruction.opcode)) {
+            if let Some(address) = machine.operand_address(pc + 1 + i, mode) {
+                self.data.insert(address);
+            }
+        }
+
+        // Jumps on an immediate condition always go the same way, they
+        // aren't branches
+        if (instruction.opcode == 5 || instruction.opcode == 6) && instruction.mode1 != 1 {
+            let condition = machine.operand_value(pc + 1, instruction.mode1);
+            let taken = (condition != 0) == (instruction.opcode == 5);
+            let branch = self.branches.entry(pc).or_default();
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,567 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::Num;
+use crate::cfg::{Block, Cfg, EdgeKind};
+use crate::disasm;
+use crate::machine::{decode, Instruction};
+
+// Position mode addresses referenced at least this many times get a name
+const NAMED_REFERENCES : usize = 3;
+
+const INDENT : &str = "    ";
+
+// A function found through a call: the caller stores a return address and
+// jumps to it. Compiled functions grow the relative base on entry, keep
+// their arguments just below it and return by shrinking it back and jumping
+// to the address stored at [rb+0].
+#[derive(Debug, Clone)]
+struct Function {
+    entry : usize,
+    frame : Option<Num>,
+    blocks : BTreeSet<usize>,
+    arguments : usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Loop {
+    header : usize,
+    exit : usize,
+}
+
+fn word(memory : &[Num], position : usize) -> Num {
+    memory.get(position).copied().unwrap_or(0)
+}
+
+struct Decompiler<'a> {
+    memory : &'a [Num],
+    cfg : Cfg,
+    functions : BTreeMap<usize, Function>,
+    names : BTreeMap<usize, (String, usize)>,
+    // Instructions that are part of a construct and aren't printed
+    implicit : HashSet<usize>,
+    labels : BTreeSet<usize>,
+    out : String,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(memory : &'a [Num]) -> Decompiler<'a> {
+        let cfg = Cfg::build(memory);
+        let mut decompiler = Decompiler {
+            memory,
+            cfg,
+            functions : BTreeMap::new(),
+            names : BTreeMap::new(),
+            implicit : HashSet::new(),
+            labels : BTreeSet::new(),
+            out : String::new(),
+        };
+        decompiler.find_functions();
+        decompiler.name_variables();
+        decompiler
+    }
+
+    fn instruction(&self, pc : usize) -> Instruction {
+        decode(word(self.memory, pc))
+    }
+
+    fn last(&self, block : &Block) -> usize {
+        *block.instructions.last().unwrap()
+    }
+
+    fn is_call(block : &Block) -> bool {
+        block.edges.iter().any(|edge| edge.kind == EdgeKind::Return)
+    }
+
+    fn call_target(block : &Block) -> Option<usize> {
+        if Decompiler::is_call(block) {
+            block.edges.iter().find(|edge| edge.kind == EdgeKind::Taken).map(|edge| edge.to)
+        } else {
+            None
+        }
+    }
+
+    // An unconditional jump to [rb+0]
+    fn is_return(&self, pc : usize) -> bool {
+        let instruction = self.instruction(pc);
+        let jumps = match instruction.opcode {
+            5 => instruction.mode1 == 1 && word(self.memory, pc + 1) != 0,
+            6 => instruction.mode1 == 1 && word(self.memory, pc + 1) == 0,
+            _ => false,
+        };
+        jumps && instruction.mode2 == 2 && word(self.memory, pc + 2) == 0
+    }
+
+    fn find_functions(&mut self) {
+        let mut entries : BTreeSet<usize> = BTreeSet::new();
+        entries.insert(0);
+        entries.extend(self.cfg.blocks.values().filter_map(Decompiler::call_target));
+
+        for &entry in &entries {
+            if !self.cfg.blocks.contains_key(&entry) {
+                continue;
+            }
+
+            // Everything reachable without following calls or entering
+            // another function
+            let mut blocks = BTreeSet::new();
+            let mut pending = vec![entry];
+            while let Some(start) = pending.pop() {
+                if !blocks.insert(start) {
+                    continue;
+                }
+                let block = &self.cfg.blocks[&start];
+                let callee = Decompiler::call_target(block);
+                for edge in &block.edges {
+                    let followed = Some(edge.to) != callee
+                        && (edge.to == entry || !entries.contains(&edge.to))
+                        && self.cfg.blocks.contains_key(&edge.to);
+                    if followed {
+                        pending.push(edge.to);
+                    }
+                }
+            }
+
+            let first = &self.cfg.blocks[&entry].instructions[0];
+            let instruction = self.instruction(*first);
+            let frame = if entry != 0 && instruction.opcode == 9 && instruction.mode1 == 1 && word(self.memory, first + 1) > 0 {
+                self.implicit.insert(*first);
+                Some(word(self.memory, first + 1))
+            } else {
+                None
+            };
+
+            self.functions.insert(entry, Function { entry, frame, blocks, arguments : 0 });
+        }
+
+        // Epilogues: shrinking the frame right before returning
+        for function in self.functions.values() {
+            let frame = match function.frame {
+                Some(frame) => frame,
+                None => continue,
+            };
+            for start in &function.blocks {
+                let block = &self.cfg.blocks[start];
+                let count = block.instructions.len();
+                if count >= 2 && self.is_return(block.instructions[count - 1]) {
+                    let pc = block.instructions[count - 2];
+                    let instruction = self.instruction(pc);
+                    if instruction.opcode == 9 && instruction.mode1 == 1 && word(self.memory, pc + 1) == -frame {
+                        self.implicit.insert(pc);
+                    }
+                }
+            }
+        }
+
+        // The widest call decides how many arguments a function takes
+        let mut arguments : BTreeMap<usize, usize> = BTreeMap::new();
+        for block in self.cfg.blocks.values() {
+            if let Some(callee) = Decompiler::call_target(block) {
+                let count = self.call_arguments(block).len();
+                let entry = arguments.entry(callee).or_insert(0);
+                *entry = (*entry).max(count);
+            }
+        }
+        for (callee, count) in arguments {
+            if let Some(function) = self.functions.get_mut(&callee) {
+                function.arguments = count;
+            }
+        }
+    }
+
+    // The instruction writing to [rb+offset], if it is a plain store
+    fn relative_store(&self, pc : usize) -> Option<Num> {
+        let instruction = self.instruction(pc);
+        match instruction.opcode {
+            1 | 2 | 7 | 8 if instruction.mode3 == 2 => Some(word(self.memory, pc + 3)),
+            3 if instruction.mode1 == 2 => Some(word(self.memory, pc + 1)),
+            _ => None,
+        }
+    }
+
+    // In a call block, the stores into [rb+1], [rb+2]... right before the
+    // return address is stored into [rb+0]
+    fn call_arguments(&self, block : &Block) -> Vec<usize> {
+        let count = block.instructions.len();
+        if count < 2 || self.relative_store(block.instructions[count - 2]) != Some(0) {
+            return Vec::new();
+        }
+
+        let mut arguments = Vec::new();
+        for &pc in block.instructions[..count - 2].iter().rev() {
+            match self.relative_store(pc) {
+                Some(offset) if offset >= 1 && !arguments.iter().any(|&(o, _)| o == offset) => arguments.push((offset, pc)),
+                _ => break,
+            }
+        }
+        arguments.sort();
+        // Only a run of consecutive slots from 1 counts as arguments
+        let run = arguments.iter().enumerate().take_while(|(i, (offset, _))| *offset == *i as Num + 1).count();
+        arguments.truncate(run);
+        arguments.into_iter().map(|(_, pc)| pc).collect()
+    }
+
+    fn name_variables(&mut self) {
+        let mut references : BTreeMap<usize, usize> = BTreeMap::new();
+
+        for block in self.cfg.blocks.values() {
+            for &pc in &block.instructions {
+                let instruction = self.instruction(pc);
+                let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+                for (i, &mode) in modes.iter().enumerate().take(disasm::parameters(instruction.opcode)) {
+                    let address = word(self.memory, pc + 1 + i);
+                    if mode == 0 && address >= 0 {
+                        *references.entry(address as usize).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        for (address, count) in references {
+            if count >= NAMED_REFERENCES {
+                self.names.insert(address, (format!("var_{}", address), count));
+            }
+        }
+    }
+
+    fn operand(&self, function : &Function, position : usize, mode : Num) -> String {
+        let value = word(self.memory, position);
+        match mode {
+            0 if value >= 0 => match self.names.get(&(value as usize)) {
+                Some((name, _)) => name.clone(),
+                None => format!("mem[{}]", value),
+            },
+            1 => value.to_string(),
+            2 => match function.frame {
+                // Arguments sit right below the frame, [rb-frame] holds the
+                // return address
+                Some(frame) if value < 0 && value + frame >= 1 => format!("arg{}", value + frame),
+                Some(frame) if value + frame == 0 => String::from("return_address"),
+                _ if value >= 0 => format!("local{}", value),
+                _ => format!("rb[{}]", value),
+            },
+            _ => disasm::operand(value, mode),
+        }
+    }
+
+    fn statement(&self, function : &Function, pc : usize) -> String {
+        let instruction = self.instruction(pc);
+        let operand = |i : usize, mode : Num| self.operand(function, pc + i, mode);
+
+        match instruction.opcode {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (operand(1, instruction.mode1), operand(2, instruction.mode2));
+                let value = match instruction.opcode {
+                    1 if a == "0" => b,
+                    1 if b == "0" => a,
+                    1 if b.starts_with('-') => format!("{} - {}", a, &b[1..]),
+                    1 => format!("{} + {}", a, b),
+                    2 if a == "1" => b,
+                    2 if b == "1" => a,
+                    2 => format!("{} * {}", a, b),
+                    7 => format!("{} < {}", a, b),
+                    _ => format!("{} == {}", a, b),
+                };
+                format!("{} = {}", operand(3, instruction.mode3), value)
+            },
+            3 => format!("{} = input()", operand(1, instruction.mode1)),
+            4 => format!("output({})", operand(1, instruction.mode1)),
+            9 => match word(self.memory, pc + 1) {
+                delta if instruction.mode1 == 1 && delta < 0 => format!("rb -= {}", -delta),
+                _ => format!("rb += {}", operand(1, instruction.mode1)),
+            },
+            99 => String::from("halt"),
+            _ => format!("// {}", disasm::instruction_at(self.memory, pc).0),
+        }
+    }
+
+    // The condition under which the jump at pc is taken, or None when it
+    // is always taken
+    fn condition(&self, function : &Function, pc : usize, negate : bool) -> Option<String> {
+        let instruction = self.instruction(pc);
+        if instruction.mode1 == 1 {
+            return None;
+        }
+        let nonzero = (instruction.opcode == 5) != negate;
+        let value = self.operand(function, pc + 1, instruction.mode1);
+        Some(format!("{} {} 0", value, if nonzero { "!=" } else { "==" }))
+    }
+
+    fn line(&mut self, indent : usize, text : &str) {
+        for _ in 0..indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // The first block of the function starting in [start, end)
+    fn next_block(&self, function : &Function, start : usize, end : usize) -> Option<usize> {
+        function.blocks.range(start..end).next().copied()
+    }
+
+    // The end of the loop headed at header: the end of the last block in the
+    // range that jumps back to it
+    fn loop_end(&self, function : &Function, header : usize, end : usize) -> Option<usize> {
+        function.blocks.range(header..end).rev()
+            .map(|start| &self.cfg.blocks[start])
+            .find(|block| !Decompiler::is_call(block)
+                && block.edges.iter().any(|edge| edge.kind == EdgeKind::Taken && edge.to == header))
+            .map(|block| block.end)
+    }
+
+    fn jump_target(&self, block : &Block) -> Option<usize> {
+        block.edges.iter().find(|edge| edge.kind == EdgeKind::Taken).map(|edge| edge.to)
+    }
+
+    fn emit_range(&mut self, function : &Function, start : usize, end : usize, indent : usize, current : Option<Loop>) {
+        let mut pc = start;
+
+        while let Some(block_start) = self.next_block(function, pc, end) {
+            let in_loop_header = current.is_some_and(|l| l.header == block_start);
+            if !in_loop_header {
+                if let Some(loop_end) = self.loop_end(function, block_start, end) {
+                    self.emit_loop(function, block_start, loop_end, indent);
+                    pc = loop_end;
+                    continue;
+                }
+            }
+
+            let block = self.cfg.blocks[&block_start].clone();
+            if self.labels.contains(&block_start) {
+                self.line(indent.saturating_sub(1), &format!("L{}:", block_start));
+            }
+
+            let last = self.last(&block);
+            let jumps = matches!(self.instruction(last).opcode, 5 | 6) && !block.invalid;
+            let call = Decompiler::call_target(&block);
+            let arguments = if call.is_some() { self.call_arguments(&block) } else { Vec::new() };
+
+            for &instruction in &block.instructions {
+                let skipped = self.implicit.contains(&instruction)
+                    || arguments.contains(&instruction)
+                    // The return address store and the jump of a call
+                    || (call.is_some() && instruction + 4 == last && self.relative_store(instruction) == Some(0))
+                    || (jumps && instruction == last);
+                if !skipped {
+                    let text = self.statement(function, instruction);
+                    self.line(indent, &text);
+                }
+            }
+
+            if block.invalid {
+                let text = format!("// invalid instruction: {}", disasm::instruction_at(self.memory, last).0);
+                self.line(indent, &text);
+                pc = block.end;
+                continue;
+            }
+
+            if let Some(callee) = call {
+                let values : Vec<String> = arguments.iter().map(|&pc| {
+                    let text = self.statement(function, pc);
+                    text.split(" = ").nth(1).unwrap_or("").to_string()
+                }).collect();
+                self.line(indent, &format!("{}({})", function_name(callee), values.join(", ")));
+                pc = block.end;
+                continue;
+            }
+
+            if !jumps {
+                pc = block.end;
+                continue;
+            }
+
+            pc = self.emit_jump(function, &block, end, indent, current);
+        }
+    }
+
+    fn emit_loop(&mut self, function : &Function, header : usize, end : usize, indent : usize) {
+        // The block closing the loop decides its shape: a conditional jump
+        // back makes a do/while, an unconditional one a plain loop
+        let latch_start = *function.blocks.range(header..end).next_back().unwrap();
+        let latch = self.cfg.blocks[&latch_start].clone();
+        let last = self.last(&latch);
+        let condition = self.condition(function, last, false);
+
+        self.implicit.insert(last);
+        self.line(indent, if condition.is_some() { "do {" } else { "loop {" });
+        self.emit_range(function, header, end, indent + 1, Some(Loop { header, exit : end }));
+        match condition {
+            Some(condition) => self.line(indent, &format!("}} while ({})", condition)),
+            None => self.line(indent, "}"),
+        }
+    }
+
+    // Emits the jump ending block and returns where emission continues
+    fn emit_jump(&mut self, function : &Function, block : &Block, end : usize, indent : usize, current : Option<Loop>) -> usize {
+        let last = self.last(block);
+
+        if self.implicit.contains(&last) {
+            return block.end;
+        }
+        if self.is_return(last) && function.frame.is_some() {
+            self.line(indent, "return");
+            return block.end;
+        }
+
+        let target = match self.jump_target(block) {
+            Some(target) => target,
+            None => {
+                let instruction = self.instruction(last);
+                let address = self.operand(function, last + 2, instruction.mode2);
+                let text = match self.condition(function, last, false) {
+                    Some(condition) => format!("if ({}) goto *{}", condition, address),
+                    None => format!("goto *{}", address),
+                };
+                self.line(indent, &text);
+                return block.end;
+            },
+        };
+        let condition = self.condition(function, last, false);
+
+        let guard = |text : &str| match &condition {
+            Some(condition) => format!("if ({}) {}", condition, text),
+            None => text.to_string(),
+        };
+
+        if let Some(current) = current {
+            if target == current.header {
+                self.line(indent, &guard("continue"));
+                return block.end;
+            }
+            if target == current.exit {
+                self.line(indent, &guard("break"));
+                return block.end;
+            }
+        }
+
+        // A conditional jump forward skips the code up to its target: an if.
+        // When that code ends by jumping over some more, those are the else.
+        if let Some(positive) = condition.as_ref().filter(|_| target > block.end && target <= end) {
+            let negated = self.condition(function, last, true).unwrap();
+            let then_last = function.blocks.range(block.end..target).next_back().copied();
+            let otherwise = then_last.and_then(|start| {
+                let then_block = &self.cfg.blocks[&start];
+                let then_jump = self.last(then_block);
+                let unconditional = matches!(self.instruction(then_jump).opcode, 5 | 6)
+                    && self.condition(function, then_jump, false).is_none()
+                    && !Decompiler::is_call(then_block);
+                match self.jump_target(then_block) {
+                    Some(join) if unconditional && join > target && join <= end
+                        && current.is_none_or(|l| join != l.exit) => Some((then_jump, join)),
+                    _ => None,
+                }
+            });
+
+            let before = self.out.len();
+            self.line(indent, &format!("if ({}) {{", negated));
+            let opened = self.out.len();
+            return match otherwise {
+                Some((then_jump, join)) => {
+                    self.implicit.insert(then_jump);
+                    self.fall_into(function, target, join);
+                    self.emit_range(function, block.end, target, indent + 1, current);
+                    if self.out.len() == opened {
+                        // Nothing to do when the jump isn't taken, flip the if
+                        self.out.truncate(before);
+                        self.line(indent, &format!("if ({}) {{", positive));
+                    } else {
+                        self.line(indent, "} else {");
+                    }
+                    self.emit_range(function, target, join, indent + 1, current);
+                    self.line(indent, "}");
+                    join
+                },
+                None => {
+                    self.fall_into(function, block.end, target);
+                    self.emit_range(function, block.end, target, indent + 1, current);
+                    self.line(indent, "}");
+                    target
+                },
+            };
+        }
+
+        self.labels.insert(target);
+        self.line(indent, &guard(&format!("goto L{}", target)));
+        block.end
+    }
+
+    // A range that ends by jumping to where it ends anyway doesn't need the
+    // jump
+    fn fall_into(&mut self, function : &Function, start : usize, end : usize) {
+        if let Some(&last_start) = function.blocks.range(start..end).next_back() {
+            let block = &self.cfg.blocks[&last_start];
+            let last = self.last(block);
+            let unconditional = matches!(self.instruction(last).opcode, 5 | 6)
+                && self.condition(function, last, false).is_none();
+            if unconditional && !Decompiler::is_call(block) && block.end <= end && self.jump_target(block) == Some(end) {
+                self.implicit.insert(last);
+            }
+        }
+    }
+
+    fn emit_function(&mut self, function : &Function) {
+        let name = function_name(function.entry);
+        let arguments : Vec<String> = (1..=function.arguments).map(|i| format!("arg{}", i)).collect();
+        let header = match function.frame {
+            Some(frame) => format!("fn {}({}) {{ // frame of {}", name, arguments.join(", "), frame),
+            None => format!("fn {}({}) {{", name, arguments.join(", ")),
+        };
+        self.line(0, &header);
+
+        let start = *function.blocks.iter().next().unwrap();
+        let end = function.blocks.iter().map(|start| self.cfg.blocks[start].end).max().unwrap();
+        self.emit_range(function, start, end, 1, None);
+
+        self.line(0, "}");
+    }
+
+    fn decompile(&mut self) -> String {
+        let functions : Vec<Function> = self.functions.values().cloned().collect();
+
+        // Labels are only known once their gotos are emitted, go again until
+        // every label has been printed
+        let implicit = self.implicit.clone();
+        loop {
+            let labels = self.labels.clone();
+            self.out.clear();
+            self.implicit = implicit.clone();
+
+            if !self.names.is_empty() {
+                self.line(0, "// Variables");
+                let names : Vec<(usize, String, usize)> = self.names.iter()
+                    .map(|(&address, (name, count))| (address, name.clone(), *count))
+                    .collect();
+                for (address, name, count) in names {
+                    self.line(0, &format!("//   {} = mem[{}], {} references", name, address, count));
+                }
+                self.line(0, "");
+            }
+
+            for (i, function) in functions.iter().enumerate() {
+                if i > 0 {
+                    self.line(0, "");
+                }
+                self.emit_function(function);
+            }
+
+            if self.labels == labels {
+                return self.out.clone();
+            }
+        }
+    }
+}
+
+fn function_name(entry : usize) -> String {
+    if entry == 0 {
+        String::from("main")
+    } else {
+        format!("fn_{}", entry)
+    }
+}
+
+// Lifts a program into structured pseudo code: loops, if/else, calls to the
+// functions it finds and names for the most used addresses. Anything that
+// doesn't fit those shapes is left as a goto.
+pub fn decompile(memory : &[Num]) -> String {
+    Decompiler::new(memory).decompile()
+}
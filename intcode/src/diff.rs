@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use crate::Num;
+use crate::machine::Machine;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub line : usize,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed snapshot at line {}", self.line)
+    }
+}
+
+impl Error for FormatError {}
+
+// What a machine looked like at some point, with the pc of the instruction
+// that last wrote each address, as far as that is known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory : Vec<Num>,
+    pub pc : usize,
+    pub relative_base : Num,
+    pub writers : BTreeMap<usize, usize>,
+}
+
+// An address holding a different value in the newer snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address : usize,
+    pub old : Num,
+    pub new : Num,
+    pub writer : Option<usize>,
+}
+
+// Changes to consecutive addresses, like the fields of a record or a row of
+// the screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range {
+    pub start : usize,
+    pub end : usize,
+    pub changes : Vec<Change>,
+}
+
+impl Range {
+    // The instructions that wrote into the range, each listed once where it
+    // first wrote, going up through the addresses
+    pub fn writers(&self) -> Vec<usize> {
+        let mut writers = Vec::new();
+        for writer in self.changes.iter().filter_map(|change| change.writer) {
+            if !writers.contains(&writer) {
+                writers.push(writer);
+            }
+        }
+        writers
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff {
+    pub pc : (usize, usize),
+    pub relative_base : (Num, Num),
+    pub changes : Vec<Change>,
+}
+
+impl Snapshot {
+    // The machine as it is now. The writers come from its history, so they
+    // are only known when history is on.
+    pub fn of(machine : &Machine) -> Snapshot {
+        let mut writers = BTreeMap::new();
+        if let Some(history) = machine.history() {
+            for write in history.records().filter_map(|record| record.write) {
+                writers.insert(write.address, write.pc);
+            }
+        }
+
+        Snapshot {
+            memory : machine.memory().to_vec(),
+            pc : machine.pc(),
+            relative_base : machine.relative_base(),
+            writers,
+        }
+    }
+
+    pub fn save(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "pc {}", self.pc).unwrap();
+        writeln!(out, "rb {}", self.relative_base).unwrap();
+        let memory : Vec<String> = self.memory.iter().map(|value| value.to_string()).collect();
+        writeln!(out, "memory {}", memory.join(",")).unwrap();
+        for (address, pc) in &self.writers {
+            writeln!(out, "writer {} {}", address, pc).unwrap();
+        }
+        out
+    }
+
+    pub fn parse(text : &str) -> Result<Snapshot, FormatError> {
+        let mut snapshot = Snapshot::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let error = FormatError { line : i + 1 };
+            let fields : Vec<&str> = line.split_whitespace().collect();
+            let number = |index : usize| -> Result<Num, FormatError> {
+                fields.get(index).and_then(|field| field.parse().ok()).ok_or_else(|| error.clone())
+            };
+            let address = |index : usize| -> Result<usize, FormatError> {
+                fields.get(index).and_then(|field| field.parse().ok()).ok_or_else(|| error.clone())
+            };
+
+            match (fields.first(), fields.len()) {
+                (None, _) => {},
+                (Some(&"pc"), 2) => snapshot.pc = address(1)?,
+                (Some(&"rb"), 2) => snapshot.relative_base = number(1)?,
+                (Some(&"memory"), 1) => snapshot.memory.clear(),
+                (Some(&"memory"), 2) => {
+                    snapshot.memory = fields[1].split(',').map(|word| word.parse().ok())
+                        .collect::<Option<Vec<Num>>>().ok_or_else(|| error.clone())?;
+                },
+                (Some(&"writer"), 3) => { snapshot.writers.insert(address(1)?, address(2)?); },
+                _ => return Err(error),
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+// Every address whose value differs between the two snapshots, annotated
+// with the last writer known to the newer one. Memory past the end of the
+// shorter snapshot reads as zero, like it does on the machine.
+pub fn diff(old : &Snapshot, new : &Snapshot) -> Diff {
+    let word = |memory : &[Num], address : usize| memory.get(address).copied().unwrap_or(0);
+    let length = old.memory.len().max(new.memory.len());
+
+    let changes = (0..length)
+        .filter(|&address| word(&old.memory, address) != word(&new.memory, address))
+        .map(|address| Change {
+            address,
+            old : word(&old.memory, address),
+            new : word(&new.memory, address),
+            writer : new.writers.get(&address).copied(),
+        })
+        .collect();
+
+    Diff {
+        pc : (old.pc, new.pc),
+        relative_base : (old.relative_base, new.relative_base),
+        changes,
+    }
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // The changes grouped into runs of consecutive addresses
+    pub fn ranges(&self) -> Vec<Range> {
+        let mut ranges : Vec<Range> = Vec::new();
+
+        for &change in &self.changes {
+            match ranges.last_mut() {
+                Some(range) if range.end + 1 == change.address => {
+                    range.end = change.address;
+                    range.changes.push(change);
+                },
+                _ => ranges.push(Range { start : change.address, end : change.address, changes : vec![change] }),
+            }
+        }
+        ranges
+    }
+
+    // Every instruction that wrote a changed address
+    pub fn writers(&self) -> BTreeSet<usize> {
+        self.changes.iter().filter_map(|change| change.writer).collect()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::Num;
+use crate::machine::decode;
+
+pub fn mnemonic(opcode : Num) -> Option<&'static str> {
+    match opcode {
+        1 => Some("add"),
+        2 => Some("mul"),
+        3 => Some("in"),
+        4 => Some("out"),
+        5 => Some("jnz"),
+        6 => Some("jz"),
+        7 => Some("lt"),
+        8 => Some("eq"),
+        9 => Some("arb"),
+        99 => Some("halt"),
+        _ => None,
+    }
+}
+
+pub fn parameters(opcode : Num) -> usize {
+    match opcode {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        3 | 4 | 9 => 1,
+        _ => 0,
+    }
+}
+
+// Position operands are shown as [12], immediates as 12 and relative
+// operands as [rb+12]
+pub fn operand(value : Num, mode : Num) -> String {
+    match mode {
+        0 => format!("[{}]", value),
+        1 => format!("{}", value),
+        2 if value < 0 => format!("[rb{}]", value),
+        2 => format!("[rb+{}]", value),
+        _ => format!("?{}:{}", mode, value),
+    }
+}
+
+// The length of the instruction at pc, or None for words that don't decode
+// to an instruction or whose operands would run past the end of memory
+pub fn length_at(memory : &[Num], pc : usize) -> Option<usize> {
+    let word = memory.get(pc).copied().unwrap_or(0);
+    let opcode = decode(word).opcode;
+    let count = parameters(opcode);
+    if mnemonic(opcode).is_some() && word >= 0 && pc + count < memory.len() {
+        Some(1 + count)
+    } else {
+        None
+    }
+}
+
+// Disassembles the instruction at pc, returning its text and length. Words
+// that length_at rejects are shown as data.
+pub fn instruction_at(memory : &[Num], pc : usize) -> (String, usize) {
+    let word = |position : usize| memory.get(position).copied().unwrap_or(0);
+    let instruction = decode(word(pc));
+    let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+
+    let count = parameters(instruction.opcode);
+    let name = match (mnemonic(instruction.opcode), length_at(memory, pc)) {
+        (Some(name), Some(_)) => name,
+        _ => return (format!("data {}", word(pc)), 1),
+    };
+
+    let operands : Vec<String> = (0..count).map(|i| operand(word(pc + 1 + i), modes[i])).collect();
+
+    if operands.is_empty() {
+        (name.to_string(), 1)
+    } else {
+        (format!("{} {}", name, operands.join(", ")), 1 + count)
+    }
+}
+
+// Disassembles linearly from start up to end, one line per instruction
+pub fn disassemble(memory : &[Num], start : usize, end : usize) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pc = start;
+
+    while pc < end {
+        let (text, length) = instruction_at(memory, pc);
+        lines.push((pc, text));
+        pc += length;
+    }
+    lines
+}
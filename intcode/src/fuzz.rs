@@ -0,0 +1,601 @@
+use std::fmt;
+
+use crate::Num;
+use crate::disasm::parameters;
+use crate::machine::{self, decode, Machine, Status};
+use crate::threaded::Threaded;
+
+// Random programs are small, anything touching memory beyond this is
+// treated as a runaway address instead of being allocated
+pub const MEMORY_LIMIT : Num = 1 << 16;
+
+// xorshift64*, good enough for generating programs and reproducible from a seed
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state : u64,
+}
+
+impl Rng {
+    pub fn new(seed : u64) -> Rng {
+        // The state must never be zero
+        Rng { state : seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n : u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    // A value in [low, high), which must not be empty
+    pub fn range(&mut self, low : Num, high : Num) -> Num {
+        low + self.below((high - low) as u64) as Num
+    }
+
+    pub fn pick<T : Copy>(&mut self, items : &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Number of instructions before the final halt
+    pub instructions : usize,
+    // Number of data words after the code. Without self modifying code
+    // writes can only go there, so there is always at least one.
+    pub data : usize,
+    pub inputs : usize,
+    // Use the relative mode and the relative base instruction
+    pub relative : bool,
+    // Allow writes into the code
+    pub self_modifying : bool,
+    // Instructions each implementation may execute
+    pub fuel : usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            instructions : 16,
+            data : 8,
+            inputs : 4,
+            relative : true,
+            self_modifying : false,
+            fuel : 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub program : Vec<Num>,
+    pub inputs : Vec<Num>,
+}
+
+// Generates a well formed program: every instruction is valid, jumps land on
+// instructions, writes never use the immediate mode and the relative base
+// only grows, so relative writes stay in the data unless self modifying code
+// is allowed.
+pub fn generate(rng : &mut Rng, config : &Config) -> Case {
+    let mut opcodes = vec![1, 2, 3, 4, 5, 6, 7, 8];
+    if config.relative {
+        opcodes.push(9);
+    }
+
+    let mut code = Vec::with_capacity(config.instructions + 1);
+    for _ in 0..config.instructions {
+        code.push(rng.pick(&opcodes));
+    }
+    code.push(99);
+
+    let mut starts = Vec::with_capacity(code.len());
+    let mut code_len = 0;
+    for &opcode in &code {
+        starts.push(code_len as Num);
+        code_len += 1 + parameters(opcode);
+    }
+    let data = if config.self_modifying { config.data } else { config.data.max(1) };
+    let size = (code_len + data) as Num;
+    let code_len = code_len as Num;
+
+    let read_modes : &[Num] = if config.relative { &[0, 1, 2] } else { &[0, 1] };
+    let write_modes : &[Num] = if config.relative { &[0, 2] } else { &[0] };
+
+    let mut program = Vec::with_capacity(size as usize);
+    for &opcode in &code {
+        let mut modes = [0; 3];
+        let mut operands = [0; 3];
+
+        for (i, (mode, operand)) in modes.iter_mut().zip(operands.iter_mut()).enumerate().take(parameters(opcode)) {
+            let writes = (i == 2) || opcode == 3;
+            let jump_target = (opcode == 5 || opcode == 6) && i == 1;
+
+            if jump_target {
+                // Jump targets come straight from the code so they always
+                // land on an instruction
+                *mode = 1;
+                *operand = rng.pick(&starts);
+            } else if opcode == 9 {
+                *mode = 1;
+                *operand = rng.range(0, 4);
+            } else {
+                *mode = rng.pick(if writes { write_modes } else { read_modes });
+                let lowest = if writes && !config.self_modifying { code_len } else { 0 };
+                *operand = match *mode {
+                    0 => rng.range(lowest, size),
+                    1 => rng.range(-10, 100),
+                    _ => rng.range(lowest, size),
+                };
+            }
+        }
+
+        program.push(opcode + modes[0] * 100 + modes[1] * 1000 + modes[2] * 10000);
+        program.extend_from_slice(&operands[..parameters(opcode)]);
+    }
+
+    for _ in 0..data {
+        program.push(rng.range(-10, 100));
+    }
+
+    let inputs = (0..config.inputs).map(|_| rng.range(-10, 100)).collect();
+
+    Case { program, inputs }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Halted,
+    NeedsInput,
+    OutOfFuel,
+    UnknownOpcode,
+    InvalidMode,
+    NegativeAddress,
+    RunawayAddress,
+}
+
+impl From<machine::Error> for End {
+    fn from(e : machine::Error) -> End {
+        match e {
+            machine::Error::UnknownOpcode { .. } => End::UnknownOpcode,
+            machine::Error::InvalidMode { .. } => End::InvalidMode,
+            machine::Error::NegativeAddress { .. } => End::NegativeAddress,
+            // Only machines told not to wait for input fail without it
+            machine::Error::NoInput { .. } => End::NeedsInput,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub end : End,
+    pub outputs : Vec<Num>,
+    pub memory : Vec<Num>,
+}
+
+impl Outcome {
+    fn new(end : End, outputs : Vec<Num>, memory : &[Num]) -> Outcome {
+        // Memory grows with zeros on access, only compare up to the last non
+        // zero word so implementations that grow differently still agree
+        let used = memory.iter().rposition(|&word| word != 0).map_or(0, |last| last + 1);
+        Outcome { end, outputs, memory : memory[..used].to_vec() }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} with outputs {:?}", self.end, self.outputs)
+    }
+}
+
+pub trait Implementation {
+    fn name(&self) -> String;
+    fn execute(&self, case : &Case, fuel : usize) -> Outcome;
+}
+
+// The furthest address the instruction at pc may touch, so runaway addresses
+// can be stopped before memory is grown to reach them
+fn furthest_address(memory : &[Num], pc : usize, relative_base : Num, relative : bool) -> Num {
+    let word = |position : usize| memory.get(position).copied().unwrap_or(0);
+    let instruction = decode(word(pc));
+    let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+
+    let mut furthest = (pc + 3) as Num;
+    for (i, &mode) in modes.iter().enumerate().take(parameters(instruction.opcode)) {
+        let operand = word(pc + 1 + i);
+        let address = match mode {
+            0 => operand,
+            2 if relative => operand.wrapping_add(relative_base),
+            _ => 0,
+        };
+        furthest = furthest.max(address);
+    }
+    furthest
+}
+
+// How inputs are handed to the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feed {
+    // Everything is queued before running
+    Upfront,
+    // One value at a time, only when the machine asks for it
+    OnDemand,
+}
+
+pub struct Vm(pub Feed);
+
+// The machine with its decode cache enabled and inputs queued upfront
+pub struct CachedVm;
+
+// The threaded engine with inputs queued upfront
+pub struct ThreadedVm;
+
+// What run_machine needs from an engine
+trait Engine {
+    fn pc(&self) -> usize;
+    fn memory(&self) -> &[Num];
+    fn relative_base(&self) -> Num;
+    fn push_input(&mut self, value : Num);
+    fn take_outputs(&mut self) -> Vec<Num>;
+    fn step(&mut self) -> Result<Option<Status>, machine::Error>;
+}
+
+macro_rules! engine {
+    ($engine:ty) => {
+        impl Engine for $engine {
+            fn pc(&self) -> usize { <$engine>::pc(self) }
+            fn memory(&self) -> &[Num] { <$engine>::memory(self) }
+            fn relative_base(&self) -> Num { <$engine>::relative_base(self) }
+            fn push_input(&mut self, value : Num) { <$engine>::push_input(self, value) }
+            fn take_outputs(&mut self) -> Vec<Num> { <$engine>::take_outputs(self) }
+            fn step(&mut self) -> Result<Option<Status>, machine::Error> { <$engine>::step(self) }
+        }
+    };
+}
+
+engine!(Machine);
+engine!(Threaded);
+
+fn run_machine<E : Engine>(mut machine : E, feed : Feed, case : &Case, fuel : usize) -> Outcome {
+    let mut inputs = case.inputs.iter();
+
+    if feed == Feed::Upfront {
+        inputs.by_ref().for_each(|&input| machine.push_input(input));
+    }
+
+    let mut end = End::OutOfFuel;
+    let mut remaining = fuel;
+    while remaining > 0 {
+        let pc = machine.pc();
+        if pc as Num > MEMORY_LIMIT
+            || furthest_address(machine.memory(), pc, machine.relative_base(), true) > MEMORY_LIMIT {
+            end = End::RunawayAddress;
+            break;
+        }
+
+        match machine.step() {
+            Ok(None) => remaining -= 1,
+            Ok(Some(Status::NeedsInput)) => match inputs.next() {
+                Some(&input) => machine.push_input(input),
+                None => {
+                    end = End::NeedsInput;
+                    break;
+                },
+            },
+            Ok(Some(Status::Halted)) => {
+                end = End::Halted;
+                break;
+            },
+            Ok(Some(Status::BudgetExceeded)) => unreachable!("step has no budget"),
+            Err(e) => {
+                end = e.into();
+                break;
+            },
+        }
+    }
+
+    Outcome::new(end, machine.take_outputs(), machine.memory())
+}
+
+impl Implementation for Vm {
+    fn name(&self) -> String {
+        format!("machine ({:?} inputs)", self.0)
+    }
+
+    fn execute(&self, case : &Case, fuel : usize) -> Outcome {
+        run_machine(Machine::new(case.program.clone()), self.0, case, fuel)
+    }
+}
+
+impl Implementation for CachedVm {
+    fn name(&self) -> String {
+        String::from("machine (decode cache)")
+    }
+
+    fn execute(&self, case : &Case, fuel : usize) -> Outcome {
+        run_machine(Machine::new(case.program.clone()).decode_cache(true), Feed::Upfront, case, fuel)
+    }
+}
+
+impl Implementation for ThreadedVm {
+    fn name(&self) -> String {
+        String::from("threaded")
+    }
+
+    fn execute(&self, case : &Case, fuel : usize) -> Outcome {
+        run_machine(Threaded::new(case.program.clone()), Feed::Upfront, case, fuel)
+    }
+}
+
+// Parameter mode handling to model, after the interpreters in the day crates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeRule {
+    // Any non zero mode is immediate and there is no relative base
+    Day5,
+    // 0 is position, 1 is immediate and anything else is relative
+    Day9,
+}
+
+// A reference model of how the day crates' interpreters handle modes, not
+// their code. It runs on Num, so it doesn't reproduce day5 overflowing its
+// i32 words. It halts when the pc runs off the end of memory, like they do,
+// and reports the cases they panic on as errors instead.
+pub struct Reference(pub ModeRule);
+
+struct ReferenceState<'a> {
+    rule : ModeRule,
+    memory : Vec<Num>,
+    relative_base : Num,
+    inputs : &'a [Num],
+    input_pos : usize,
+    outputs : Vec<Num>,
+}
+
+impl<'a> ReferenceState<'a> {
+    fn address(&mut self, address : Num) -> Result<usize, End> {
+        if address < 0 {
+            return Err(End::NegativeAddress);
+        }
+        let address = address as usize;
+        if address >= self.memory.len() {
+            match self.rule {
+                // day5 indexes memory directly and panics out of bounds
+                ModeRule::Day5 => return Err(End::RunawayAddress),
+                ModeRule::Day9 => self.memory.resize(address + 1, 0),
+            }
+        }
+        Ok(address)
+    }
+
+    fn get_value(&mut self, position : usize, mode : Num) -> Result<Num, End> {
+        let position = self.address(position as Num)?;
+        let memory_value = self.memory[position];
+        let address = match (self.rule, mode) {
+            (_, 0) => memory_value,
+            (ModeRule::Day5, _) | (ModeRule::Day9, 1) => return Ok(memory_value),
+            (ModeRule::Day9, _) => memory_value.wrapping_add(self.relative_base),
+        };
+        let address = self.address(address)?;
+        Ok(self.memory[address])
+    }
+
+    fn store_value(&mut self, position : usize, value : Num, mode : Num) -> Result<(), End> {
+        let position = self.address(position as Num)?;
+        let memory_value = self.memory[position];
+        let address = match (self.rule, mode) {
+            (_, 0) => memory_value,
+            (ModeRule::Day9, 2) => memory_value.wrapping_add(self.relative_base),
+            _ => return Err(End::InvalidMode),
+        };
+        let address = self.address(address)?;
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    // Executes the instruction at pc and returns the next pc, or how the
+    // program stopped
+    fn step(&mut self, pc : usize) -> Result<usize, End> {
+        let instruction = decode(self.memory[pc]);
+
+        match instruction.opcode {
+            99 => Err(End::Halted),
+            1 | 2 | 7 | 8 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                let op2 = self.get_value(pc + 2, instruction.mode2)?;
+                let result = match instruction.opcode {
+                    1 => op1.wrapping_add(op2),
+                    2 => op1.wrapping_mul(op2),
+                    7 => (op1 < op2) as Num,
+                    _ => (op1 == op2) as Num,
+                };
+                self.store_value(pc + 3, result, instruction.mode3)?;
+                Ok(pc + 4)
+            },
+            3 => {
+                let value = *self.inputs.get(self.input_pos).ok_or(End::NeedsInput)?;
+                self.input_pos += 1;
+                self.store_value(pc + 1, value, instruction.mode1)?;
+                Ok(pc + 2)
+            },
+            4 => {
+                let value = self.get_value(pc + 1, instruction.mode1)?;
+                self.outputs.push(value);
+                Ok(pc + 2)
+            },
+            5 | 6 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                let op2 = self.get_value(pc + 2, instruction.mode2)?;
+                let jump = if instruction.opcode == 5 { op1 != 0 } else { op1 == 0 };
+                // A negative target wraps around to a huge pc, which halts on
+                // the next iteration
+                Ok(if jump { op2 as usize } else { pc + 3 })
+            },
+            9 if self.rule == ModeRule::Day9 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                self.relative_base = self.relative_base.wrapping_add(op1);
+                Ok(pc + 2)
+            },
+            _ => Err(End::UnknownOpcode),
+        }
+    }
+
+    fn run(&mut self, fuel : usize) -> End {
+        let mut pc = 0;
+        let relative = self.rule == ModeRule::Day9;
+
+        for _ in 0..fuel {
+            if pc >= self.memory.len() {
+                return End::Halted;
+            }
+            if furthest_address(&self.memory, pc, self.relative_base, relative) > MEMORY_LIMIT {
+                return End::RunawayAddress;
+            }
+
+            match self.step(pc) {
+                Ok(new_pc) => pc = new_pc,
+                Err(end) => return end,
+            }
+        }
+
+        End::OutOfFuel
+    }
+}
+
+impl Implementation for Reference {
+    fn name(&self) -> String {
+        format!("{:?} reference model", self.0)
+    }
+
+    fn execute(&self, case : &Case, fuel : usize) -> Outcome {
+        let mut state = ReferenceState {
+            rule : self.0,
+            memory : case.program.clone(),
+            relative_base : 0,
+            inputs : &case.inputs,
+            input_pos : 0,
+            outputs : Vec::new(),
+        };
+        let end = state.run(fuel);
+        Outcome::new(end, state.outputs, &state.memory)
+    }
+}
+
+// Runs a case on every implementation, returning the outcomes if any of them
+// disagrees with the first
+pub fn compare(implementations : &[&dyn Implementation], case : &Case, fuel : usize) -> Option<Vec<(String, Outcome)>> {
+    let outcomes : Vec<(String, Outcome)> = implementations.iter()
+        .map(|implementation| (implementation.name(), implementation.execute(case, fuel)))
+        .collect();
+
+    if outcomes.iter().all(|(_, outcome)| *outcome == outcomes[0].1) {
+        None
+    } else {
+        Some(outcomes)
+    }
+}
+
+fn shrink_value(value : Num) -> Vec<Num> {
+    let mut candidates = vec![0];
+    if value / 2 != 0 {
+        candidates.push(value / 2);
+    }
+    if let Some(negated) = value.checked_neg().filter(|_| value < 0) {
+        candidates.push(negated);
+    }
+    candidates.push(value - value.signum());
+    candidates.retain(|&candidate| candidate != value);
+    candidates
+}
+
+// Greedily removes chunks of the program and inputs and moves words towards
+// zero for as long as the case keeps failing
+pub fn shrink<F : Fn(&Case) -> bool>(case : Case, fails : F) -> Case {
+    let mut case = case;
+
+    loop {
+        let mut progress = false;
+
+        let mut chunk = case.program.len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start + chunk <= case.program.len() {
+                let mut candidate = case.clone();
+                candidate.program.drain(start..start + chunk);
+                if !candidate.program.is_empty() && fails(&candidate) {
+                    case = candidate;
+                    progress = true;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+
+        let mut i = 0;
+        while i < case.inputs.len() {
+            let mut candidate = case.clone();
+            candidate.inputs.remove(i);
+            if fails(&candidate) {
+                case = candidate;
+                progress = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        for i in 0..case.program.len() {
+            for value in shrink_value(case.program[i]) {
+                let mut candidate = case.clone();
+                candidate.program[i] = value;
+                if fails(&candidate) {
+                    case = candidate;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        for i in 0..case.inputs.len() {
+            for value in shrink_value(case.inputs[i]) {
+                let mut candidate = case.clone();
+                candidate.inputs[i] = value;
+                if fails(&candidate) {
+                    case = candidate;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        if !progress {
+            return case;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub seed : u64,
+    pub original : Case,
+    pub shrunk : Case,
+    pub outcomes : Vec<(String, Outcome)>,
+}
+
+// Generates `cases` programs from consecutive seeds and returns the first
+// disagreement found, shrunk
+pub fn differential(implementations : &[&dyn Implementation], config : &Config, seed : u64, cases : u64) -> Option<Counterexample> {
+    for seed in seed..seed + cases {
+        let case = generate(&mut Rng::new(seed), config);
+
+        if compare(implementations, &case, config.fuel).is_some() {
+            let shrunk = shrink(case.clone(), |candidate| compare(implementations, candidate, config.fuel).is_some());
+            let outcomes = compare(implementations, &shrunk, config.fuel).unwrap();
+            return Some(Counterexample { seed, original : case, shrunk, outcomes });
+        }
+    }
+    None
+}
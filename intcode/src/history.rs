@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+use crate::Num;
+
+// A word changed by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Write {
+    // How many instructions the machine had executed before this one
+    pub step : u64,
+    pub pc : usize,
+    pub address : usize,
+    pub old : Num,
+    pub new : Num,
+}
+
+// Everything an executed instruction changed, enough to undo it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub step : u64,
+    pub pc : usize,
+    pub relative_base : Num,
+    // Memory grows on accesses past the end, undoing shrinks it back
+    pub length : usize,
+    pub outputs : usize,
+    pub input : Option<Num>,
+    pub write : Option<Write>,
+    // Words from pc on that the instruction marked as code for the first
+    // time, as a bit mask
+    pub code : u8,
+    // Whether the write landed on code and was counted as a code write
+    pub code_write : bool,
+}
+
+// The undo log of a machine, holding the last few executed instructions.
+// Older records are dropped once it is full, so memory use stays bounded
+// no matter how long the program runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    records : VecDeque<Record>,
+    capacity : usize,
+    dropped : u64,
+    // The record of the instruction being executed, kept only if it completes
+    pending : Option<Record>,
+    // Code marked by an instruction that didn't complete, like one waiting
+    // for input, as its pc and mask. It belongs to the record of the next
+    // try at that pc.
+    discarded_code : Option<(usize, u8)>,
+}
+
+impl History {
+    pub fn new(capacity : usize) -> History {
+        History {
+            records : VecDeque::with_capacity(capacity.min(1 << 16)),
+            capacity,
+            dropped : 0,
+            pending : None,
+            discarded_code : None,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // Records that fell off the end, how far back is out of reach
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Oldest first
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &Record> {
+        self.records.iter()
+    }
+
+    // The most recent write to address still in the log
+    pub fn last_write(&self, address : usize) -> Option<Write> {
+        self.records.iter().rev().filter_map(|record| record.write).find(|write| write.address == address)
+    }
+
+    pub(crate) fn begin(&mut self, mut record : Record) {
+        if let Some((pc, code)) = self.discarded_code.take() {
+            if pc == record.pc {
+                record.code |= code;
+            }
+        }
+        self.pending = Some(record);
+    }
+
+    pub(crate) fn input(&mut self, value : Num) {
+        if let Some(record) = &mut self.pending {
+            record.input = Some(value);
+        }
+    }
+
+    pub(crate) fn write(&mut self, address : usize, old : Num, new : Num) {
+        if let Some(record) = &mut self.pending {
+            record.write = Some(Write { step : record.step, pc : record.pc, address, old, new });
+        }
+    }
+
+    pub(crate) fn code(&mut self, mask : u8) {
+        if let Some(record) = &mut self.pending {
+            record.code |= mask;
+        }
+    }
+
+    pub(crate) fn code_write(&mut self) {
+        if let Some(record) = &mut self.pending {
+            record.code_write = true;
+        }
+    }
+
+    pub(crate) fn commit(&mut self) {
+        if let Some(record) = self.pending.take() {
+            if self.capacity == 0 {
+                self.dropped += 1;
+                return;
+            }
+            if self.records.len() == self.capacity {
+                self.records.pop_front();
+                self.dropped += 1;
+            }
+            self.records.push_back(record);
+        }
+    }
+
+    pub(crate) fn discard(&mut self) {
+        if let Some(record) = self.pending.take() {
+            if record.code != 0 {
+                self.discarded_code = Some((record.pc, record.code));
+            }
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Record> {
+        self.records.pop_back()
+    }
+
+    pub(crate) fn take_discarded_code(&mut self) -> Option<(usize, u8)> {
+        self.discarded_code.take()
+    }
+}
@@ -0,0 +1,18 @@
+pub mod bench;
+pub mod binary;
+pub mod cfg;
+pub mod coverage;
+pub mod decompile;
+pub mod diff;
+pub mod disasm;
+pub mod fuzz;
+pub mod history;
+pub mod loader;
+pub mod machine;
+pub mod network;
+pub mod patch;
+pub mod profile;
+pub mod threaded;
+pub mod transpile;
+
+pub type Num = i64;
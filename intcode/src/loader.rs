@@ -0,0 +1,209 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::Num;
+use crate::binary::{self, BinaryError};
+
+// Where to read a program from
+#[derive(Debug, Clone)]
+pub enum Source {
+    File(PathBuf),
+    Stdin,
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line : usize,
+    pub column : usize,
+    pub token : String,
+    pub message : &'static str,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Binary(BinaryError),
+}
+
+impl Source {
+    // "-" stands for stdin, anything else is a file name
+    pub fn from_arg(arg : &str) -> Source {
+        if arg == "-" {
+            Source::Stdin
+        } else {
+            Source::File(PathBuf::from(arg))
+        }
+    }
+
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Source::File(path) => fs::read(path),
+            Source::Stdin => {
+                let mut contents = Vec::new();
+                io::stdin().read_to_end(&mut contents)?;
+                Ok(contents)
+            },
+            Source::Text(text) => Ok(text.clone().into_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)?;
+        if !self.token.is_empty() {
+            write!(f, " {:?}", self.token)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+            LoadError::Binary(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Parse(e) => Some(e),
+            LoadError::Binary(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e : io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<BinaryError> for LoadError {
+    fn from(e : BinaryError) -> LoadError {
+        LoadError::Binary(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e : ParseError) -> LoadError {
+        LoadError::Parse(e)
+    }
+}
+
+enum Token<'a> {
+    Value(&'a str),
+    Comma,
+}
+
+// Splits a line into values and commas, with their columns, dropping comments
+fn tokenize(line : &str) -> Vec<(usize, Token<'_>)> {
+    let line = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        if c == ',' || c.is_whitespace() {
+            if let Some(start) = start.take() {
+                tokens.push((start, Token::Value(&line[start..i])));
+            }
+            if c == ',' {
+                tokens.push((i, Token::Comma));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+
+    tokens
+}
+
+// Parses comma separated values. Whitespace, newlines and comments starting
+// with '#' may appear between values, and a trailing comma is allowed.
+pub fn parse<T : FromStr>(text : &str) -> Result<Vec<T>, ParseError> {
+    let mut program = Vec::new();
+    let mut expect_value = true;
+    let mut last = (1, 1);
+
+    for (line_number, line) in text.lines().enumerate() {
+        for (offset, token) in tokenize(line) {
+            let error = |token : &str, message| ParseError {
+                line : line_number + 1,
+                column : line[..offset].chars().count() + 1,
+                token : token.to_string(),
+                message,
+            };
+
+            match token {
+                Token::Value(value) if expect_value => {
+                    let value = value.parse().map_err(|_| error(value, "invalid number"))?;
+                    program.push(value);
+                    expect_value = false;
+                },
+                Token::Value(value) => return Err(error(value, "expected ',' before")),
+                Token::Comma if expect_value => return Err(error(",", "expected a value before")),
+                Token::Comma => expect_value = true,
+            }
+        }
+        last = (line_number + 1, line.chars().count() + 1);
+    }
+
+    if program.is_empty() {
+        return Err(ParseError {
+            line : last.0,
+            column : last.1,
+            token : String::new(),
+            message : "empty program",
+        });
+    }
+
+    Ok(program)
+}
+
+// The inverse of parse
+pub fn format_text(program : &[Num]) -> String {
+    let words : Vec<String> = program.iter().map(|word| word.to_string()).collect();
+    words.join(",") + "\n"
+}
+
+// Loads either a text or a binary program, telling them apart by the header
+pub fn load<T : FromStr + TryFrom<Num>>(source : &Source) -> Result<Vec<T>, LoadError> {
+    load_bytes(source.read()?)
+}
+
+pub fn load_bytes<T : FromStr + TryFrom<Num>>(bytes : Vec<u8>) -> Result<Vec<T>, LoadError> {
+    if binary::is_binary(&bytes) {
+        return binary::decode(&bytes)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| T::try_from(value)
+                .map_err(|_| LoadError::Binary(BinaryError::OutOfRange { index, value })))
+            .collect();
+    }
+
+    let text = String::from_utf8(bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(parse(&text)?)
+}
+
+pub fn load_file<T : FromStr + TryFrom<Num>>(filename : &str) -> Result<Vec<T>, LoadError> {
+    load(&Source::File(PathBuf::from(filename)))
+}
@@ -0,0 +1,736 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::Num;
+use crate::disasm;
+use crate::history::{History, Record, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub opcode : Num,
+    pub mode1 : Num,
+    pub mode2 : Num,
+    pub mode3 : Num,
+}
+
+// Why the machine stopped running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    // An input instruction found the input queue empty. Push some input and
+    // run again to continue from the same instruction.
+    NeedsInput,
+    // A limit passed to run_with was reached. The machine is left before the
+    // next instruction and can be inspected or run again.
+    BudgetExceeded,
+}
+
+// What an input instruction does when there is no input. Only Machine has
+// a policy, the other engines always yield.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyInput {
+    // Stop with NeedsInput, to be run again once there is some
+    #[default]
+    Yield,
+    // Fail with Error::NoInput
+    Error,
+    // Wait on the channel given to connect() until a value arrives. Without a
+    // channel, or once its sender is gone, this is the same as Error.
+    Block,
+    // Read this value instead, like -1 for "no packet"
+    Default(Num),
+}
+
+// How long run_with may go on for. Both limits can be combined, whichever is
+// reached first stops the machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub instructions : Option<u64>,
+    pub deadline : Option<Instant>,
+}
+
+impl Limits {
+    pub fn none() -> Limits {
+        Limits::default()
+    }
+
+    pub fn instructions(self, instructions : u64) -> Limits {
+        Limits { instructions : Some(instructions), ..self }
+    }
+
+    pub fn timeout(self, timeout : Duration) -> Limits {
+        Limits { deadline : Some(Instant::now() + timeout), ..self }
+    }
+}
+
+// Hooks called by run_traced and step_traced as the machine runs
+pub trait Trace {
+    // Called before each instruction is executed
+    fn instruction(&mut self, _machine : &Machine, _instruction : Instruction) {}
+    // Called when an input instruction finds no input
+    fn needs_input(&mut self, _machine : &Machine) {}
+    // Called when an input instruction waited on its channel under
+    // EmptyInput::Block, with how long it waited
+    fn blocked(&mut self, _machine : &Machine, _waited : Duration) {}
+}
+
+// What anything running intcode offers, so the day binaries can swap one
+// engine for another. run stops with NeedsInput when inputs run out, except
+// on a Machine given another EmptyInput policy.
+pub trait Computer {
+    fn push_input(&mut self, value : Num);
+    fn outputs(&self) -> &[Num];
+    fn take_outputs(&mut self) -> Vec<Num>;
+    fn run(&mut self) -> Result<Status, Error>;
+    fn is_halted(&self) -> bool;
+}
+
+// Everything a machine needs to pick up where another engine left off
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    pub memory : Vec<Num>,
+    pub pc : usize,
+    pub relative_base : Num,
+    pub inputs : VecDeque<Num>,
+    pub outputs : Vec<Num>,
+    pub halted : bool,
+}
+
+// A write into a word that was part of an executed instruction, and how
+// many times the instruction at pc did it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub pc : usize,
+    pub address : usize,
+    pub count : u64,
+}
+
+// Instructions are at most this many words long
+const MAX_LENGTH : usize = 4;
+
+// Checking the clock on every instruction would slow everything down
+pub(crate) const DEADLINE_CHECK_INTERVAL : u64 = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownOpcode { pc : usize, opcode : Num },
+    InvalidMode { pc : usize, mode : Num },
+    NegativeAddress { pc : usize, address : Num },
+    // An input instruction found no input and the machine can't wait for it
+    NoInput { pc : usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {} at {}", opcode, pc),
+            Error::InvalidMode { pc, mode } => write!(f, "invalid parameter mode {} at {}", mode, pc),
+            Error::NegativeAddress { pc, address } => write!(f, "negative address {} at {}", address, pc),
+            Error::NoInput { pc } => write!(f, "no input for the instruction at {}", pc),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+pub fn decode(i : Num) -> Instruction {
+    let mut i = i;
+
+    let opcode = i % 100;
+
+    i /= 100;
+
+    let mode1 = i % 10;
+
+    i /= 10;
+
+    let mode2 = i % 10;
+
+    i /= 10;
+
+    let mode3 = i % 10;
+
+    Instruction {
+        opcode,
+        mode1,
+        mode2,
+        mode3
+    }
+}
+
+// An intcode computer with its own input queue and output buffer
+#[derive(Debug, Clone)]
+pub struct Machine {
+    memory : Vec<Num>,
+    pc : usize,
+    relative_base : Num,
+    inputs : VecDeque<Num>,
+    outputs : Vec<Num>,
+    halted : bool,
+    executed : u64,
+    // Words belonging to an instruction the machine has executed
+    code : Vec<bool>,
+    code_writes : BTreeMap<(usize, usize), u64>,
+    // Decoded instructions by pc, only filled when the cache is enabled
+    decoded : Option<Vec<Option<Instruction>>>,
+    // Undo log for stepping backwards, only kept when enabled
+    history : Option<History>,
+    empty_input : EmptyInput,
+    channels : Option<Channels>,
+}
+
+// Where a connected machine reads inputs from once its queue is empty, and
+// where it sends its outputs. Clones read from the same channel.
+#[derive(Debug, Clone)]
+struct Channels {
+    inputs : Arc<Mutex<Receiver<Num>>>,
+    outputs : Sender<Num>,
+}
+
+impl Machine {
+    pub fn new(program : Vec<Num>) -> Machine {
+        Machine {
+            memory : program,
+            pc : 0,
+            relative_base : 0,
+            inputs : VecDeque::new(),
+            outputs : Vec::new(),
+            halted : false,
+            executed : 0,
+            code : Vec::new(),
+            code_writes : BTreeMap::new(),
+            decoded : None,
+            history : None,
+            empty_input : EmptyInput::Yield,
+            channels : None,
+        }
+    }
+
+    // A machine continuing from state, as if it had run up to there itself
+    // but with no instructions executed yet
+    pub fn from_state(state : State) -> Machine {
+        Machine {
+            memory : state.memory,
+            pc : state.pc,
+            relative_base : state.relative_base,
+            inputs : state.inputs,
+            outputs : state.outputs,
+            halted : state.halted,
+            ..Machine::new(Vec::new())
+        }
+    }
+
+    pub fn into_state(self) -> State {
+        State {
+            memory : self.memory,
+            pc : self.pc,
+            relative_base : self.relative_base,
+            inputs : self.inputs,
+            outputs : self.outputs,
+            halted : self.halted,
+        }
+    }
+
+    // Decodes every instruction only once instead of on every step. Writes
+    // into executed code drop the affected entries, so self modifying
+    // programs behave the same either way.
+    pub fn decode_cache(self, enabled : bool) -> Machine {
+        Machine { decoded : if enabled { Some(Vec::new()) } else { None }, ..self }
+    }
+
+    // Keeps an undo log of the last length instructions, so the machine can
+    // step backwards through them. Zero turns the log off.
+    pub fn history_length(self, length : usize) -> Machine {
+        Machine { history : if length > 0 { Some(History::new(length)) } else { None }, ..self }
+    }
+
+    // What to do when an input instruction finds the queue empty, Yield
+    // unless set otherwise
+    pub fn empty_input(self, policy : EmptyInput) -> Machine {
+        Machine { empty_input : policy, ..self }
+    }
+
+    // Reads inputs from the receiver once the queue runs out, and sends every
+    // output to the sender as well as keeping it. Sending to a sender whose
+    // receiver is gone is not an error, the output is just kept.
+    pub fn connect(self, inputs : Receiver<Num>, outputs : Sender<Num>) -> Machine {
+        let channels = Channels { inputs : Arc::new(Mutex::new(inputs)), outputs };
+        Machine { channels : Some(channels), ..self }
+    }
+
+    pub fn push_input(&mut self, value : Num) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn outputs(&self) -> &[Num] {
+        &self.outputs
+    }
+
+    pub fn take_outputs(&mut self) -> Vec<Num> {
+        std::mem::take(&mut self.outputs)
+    }
+
+    pub fn memory(&self) -> &[Num] {
+        &self.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Num {
+        self.relative_base
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // Instructions executed since the machine was created, counting the halt
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    // Whether address was part of an instruction the machine executed
+    pub fn is_code(&self, address : usize) -> bool {
+        self.code.get(address).copied().unwrap_or(false)
+    }
+
+    // Writes that landed on executed code so far, by pc then address
+    pub fn code_writes(&self) -> Vec<CodeWrite> {
+        self.code_writes.iter().map(|(&(pc, address), &count)| CodeWrite { pc, address, count }).collect()
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    // The instruction that last wrote address, as far back as the history
+    // goes. None if the history is off or holds no write to it.
+    pub fn last_writer(&self, address : usize) -> Option<Write> {
+        self.history.as_ref().and_then(|history| history.last_write(address))
+    }
+
+    // Reads memory without growing it, words past the end read as zero
+    pub fn peek(&self, address : usize) -> Num {
+        self.memory.get(address).copied().unwrap_or(0)
+    }
+
+    // The address the operand at position refers to with the given mode, or
+    // None for immediates and addresses that can't be accessed
+    pub fn operand_address(&self, position : usize, mode : Num) -> Option<usize> {
+        let value = self.peek(position);
+        let address = match mode {
+            0 => value,
+            2 => value.wrapping_add(self.relative_base),
+            _ => return None,
+        };
+        if address < 0 { None } else { Some(address as usize) }
+    }
+
+    // The value an operand reads, without side effects
+    pub fn operand_value(&self, position : usize, mode : Num) -> Num {
+        if mode == 1 {
+            self.peek(position)
+        } else {
+            self.operand_address(position, mode).map_or(0, |address| self.peek(address))
+        }
+    }
+
+    fn address(&self, address : Num) -> Result<usize, Error> {
+        if address < 0 {
+            Err(Error::NegativeAddress { pc : self.pc, address })
+        } else {
+            Ok(address as usize)
+        }
+    }
+
+    // Memory is unbounded, reading past the end grows it with zeros
+    fn safe_get(&mut self, position : usize) -> Num {
+        if position >= self.memory.len() {
+            self.memory.resize(position + 1, 0);
+        }
+        self.memory[position]
+    }
+
+    fn safe_set(&mut self, position : usize, value : Num) {
+        if position >= self.memory.len() {
+            self.memory.resize(position + 1, 0);
+        }
+        let old = std::mem::replace(&mut self.memory[position], value);
+        if let Some(history) = &mut self.history {
+            history.write(position, old, value);
+        }
+
+        if self.is_code(position) {
+            *self.code_writes.entry((self.pc, position)).or_insert(0) += 1;
+            if let Some(history) = &mut self.history {
+                history.code_write();
+            }
+            self.invalidate(position);
+        }
+    }
+
+    // Any cached instruction covering the word is stale after it changes
+    fn invalidate(&mut self, position : usize) {
+        if let Some(decoded) = &mut self.decoded {
+            let first = position.saturating_sub(MAX_LENGTH - 1);
+            for entry in decoded.iter_mut().take(position + 1).skip(first) {
+                *entry = None;
+            }
+        }
+    }
+
+    // Decodes the instruction at pc and marks its words as code. This
+    // happens before it runs, so an instruction writing over itself is
+    // caught as well.
+    fn fetch(&mut self, pc : usize) -> Instruction {
+        if let Some(Some(instruction)) = self.decoded.as_ref().and_then(|decoded| decoded.get(pc)) {
+            return *instruction;
+        }
+
+        let instruction = decode(self.safe_get(pc));
+        let end = pc + 1 + disasm::parameters(instruction.opcode);
+        if end > self.code.len() {
+            self.code.resize(end, false);
+        }
+        let mut marked = 0;
+        for (i, word) in self.code[pc..end].iter_mut().enumerate() {
+            if !*word {
+                marked |= 1 << i;
+            }
+            *word = true;
+        }
+        if let Some(history) = &mut self.history {
+            history.code(marked);
+        }
+
+        if let Some(decoded) = &mut self.decoded {
+            if pc >= decoded.len() {
+                decoded.resize(pc + 1, None);
+            }
+            decoded[pc] = Some(instruction);
+        }
+        instruction
+    }
+
+    // Moves a value from the connected channel into the empty queue, waiting
+    // for one if the policy is to block. Returns how long it waited, if the
+    // channel was empty and it had to.
+    fn receive(&mut self) -> Option<Duration> {
+        let channels = match &self.channels {
+            Some(channels) if self.inputs.is_empty() => channels,
+            _ => return None,
+        };
+        let inputs = match channels.inputs.lock() {
+            Ok(inputs) => inputs,
+            Err(_) => return None,
+        };
+        let mut waited = None;
+        let mut value = inputs.try_recv().ok();
+        if value.is_none() && self.empty_input == EmptyInput::Block {
+            let since = Instant::now();
+            value = inputs.recv().ok();
+            waited = Some(since.elapsed());
+        }
+        drop(inputs);
+        self.inputs.extend(value);
+        waited
+    }
+
+    fn get_value(&mut self, position : usize, mode : Num) -> Result<Num, Error> {
+        let memory_value = self.safe_get(position);
+        match mode {
+            0 => { // POSITION MODE
+                let address = self.address(memory_value)?;
+                Ok(self.safe_get(address))
+            },
+            1 => Ok(memory_value), // IMMEDIATE MODE
+            2 => { // RELATIVE MODE
+                let address = self.address(memory_value.wrapping_add(self.relative_base))?;
+                Ok(self.safe_get(address))
+            },
+            _ => Err(Error::InvalidMode { pc : self.pc, mode }),
+        }
+    }
+
+    fn store_value(&mut self, position : usize, value : Num, mode : Num) -> Result<(), Error> {
+        let memory_value = self.safe_get(position);
+        let address = match mode {
+            0 => self.address(memory_value)?, // POSITION MODE
+            2 => self.address(memory_value.wrapping_add(self.relative_base))?, // RELATIVE MODE
+            _ => return Err(Error::InvalidMode { pc : self.pc, mode }),
+        };
+        self.safe_set(address, value);
+        Ok(())
+    }
+
+    // Executes a single instruction. Returns a status when the machine can't
+    // go on, either because it halted or because it is waiting for input.
+    pub fn step(&mut self) -> Result<Option<Status>, Error> {
+        self.step_with(None)
+    }
+
+    pub fn step_traced(&mut self, trace : &mut dyn Trace) -> Result<Option<Status>, Error> {
+        self.step_with(Some(trace))
+    }
+
+    fn step_with<'t>(&mut self, trace : Option<&mut (dyn Trace + 't)>) -> Result<Option<Status>, Error> {
+        if self.halted {
+            return Ok(Some(Status::Halted));
+        }
+
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return self.execute(trace),
+        };
+        history.begin(Record {
+            step : self.executed,
+            pc : self.pc,
+            relative_base : self.relative_base,
+            length : self.memory.len(),
+            outputs : self.outputs.len(),
+            input : None,
+            write : None,
+            code : 0,
+            code_write : false,
+        });
+
+        let before = self.executed;
+        let result = self.execute(trace);
+        // Only instructions that ran are kept, not ones waiting for input
+        // or failing
+        if let Some(history) = &mut self.history {
+            if self.executed > before {
+                history.commit();
+            } else {
+                history.discard();
+            }
+        }
+        result
+    }
+
+    fn execute<'t>(&mut self, trace : Option<&mut (dyn Trace + 't)>) -> Result<Option<Status>, Error> {
+        let pc = self.pc;
+        let instruction = self.fetch(pc);
+        let waited = if instruction.opcode == 3 { self.receive() } else { None };
+
+        if let Some(trace) = trace {
+            if let Some(waited) = waited {
+                trace.blocked(self, waited);
+            }
+            if instruction.opcode == 3 && self.inputs.is_empty() && self.empty_input == EmptyInput::Yield {
+                trace.needs_input(self);
+            } else {
+                trace.instruction(self, instruction);
+            }
+        }
+
+        let new_pc = match instruction.opcode {
+            1 | 2 | 7 | 8 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                let op2 = self.get_value(pc + 2, instruction.mode2)?;
+
+                // Overflow isn't defined by intcode, wrap instead of panicking
+                let result = match instruction.opcode {
+                    1 => op1.wrapping_add(op2),
+                    2 => op1.wrapping_mul(op2),
+                    7 => (op1 < op2) as Num,
+                    _ => (op1 == op2) as Num,
+                };
+
+                self.store_value(pc + 3, result, instruction.mode3)?;
+                pc + 4
+            },
+            3 => {
+                let value = match self.inputs.pop_front() {
+                    Some(value) => {
+                        if let Some(history) = &mut self.history {
+                            history.input(value);
+                        }
+                        value
+                    },
+                    None => match self.empty_input {
+                        EmptyInput::Yield => return Ok(Some(Status::NeedsInput)),
+                        EmptyInput::Default(value) => value,
+                        EmptyInput::Error | EmptyInput::Block => return Err(Error::NoInput { pc }),
+                    },
+                };
+                self.store_value(pc + 1, value, instruction.mode1)?;
+                pc + 2
+            },
+            4 => {
+                let value = self.get_value(pc + 1, instruction.mode1)?;
+                if let Some(channels) = &self.channels {
+                    let _ = channels.outputs.send(value);
+                }
+                self.outputs.push(value);
+                pc + 2
+            },
+            5 | 6 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                let op2 = self.get_value(pc + 2, instruction.mode2)?;
+
+                let jump = match instruction.opcode {
+                    5 => op1 != 0,
+                    _ => op1 == 0,
+                };
+
+                if jump {
+                    self.address(op2)?
+                } else {
+                    pc + 3
+                }
+            },
+            9 => {
+                let op1 = self.get_value(pc + 1, instruction.mode1)?;
+                self.relative_base = self.relative_base.wrapping_add(op1);
+                pc + 2
+            },
+            99 => {
+                self.executed += 1;
+                self.halted = true;
+                return Ok(Some(Status::Halted));
+            },
+            opcode => return Err(Error::UnknownOpcode { pc, opcode }),
+        };
+
+        self.executed += 1;
+        self.pc = new_pc;
+        Ok(None)
+    }
+
+    // Undoes the last executed instruction, including the words it marked as
+    // code and its code write. Returns false when the history is off or has
+    // nothing left. Outputs already taken stay taken.
+    pub fn step_back(&mut self) -> bool {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return false,
+        };
+        let record = match history.pop() {
+            Some(record) => record,
+            None => return false,
+        };
+        // An instruction that was tried but didn't run is forgotten as well
+        if let Some((pc, code)) = history.take_discarded_code() {
+            self.unmark_code(pc, code);
+        }
+
+        if let Some(write) = record.write {
+            self.memory[write.address] = write.old;
+            self.invalidate(write.address);
+            if record.code_write {
+                let key = (write.pc, write.address);
+                if let Some(count) = self.code_writes.get_mut(&key) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.code_writes.remove(&key);
+                    }
+                }
+            }
+        }
+        self.unmark_code(record.pc, record.code);
+        self.memory.truncate(record.length);
+        if let Some(value) = record.input {
+            self.inputs.push_front(value);
+        }
+        self.outputs.truncate(record.outputs);
+        self.pc = record.pc;
+        self.relative_base = record.relative_base;
+        self.executed = record.step;
+        self.halted = false;
+        true
+    }
+
+    // Forgets the words from pc on in the mask were code
+    fn unmark_code(&mut self, pc : usize, mask : u8) {
+        for i in 0..MAX_LENGTH {
+            if mask & (1 << i) != 0 {
+                self.code[pc + i] = false;
+            }
+        }
+    }
+
+    // Steps backwards until the instruction that last wrote address is
+    // undone, leaving the machine just before it ran. Returns that write, or
+    // None with the machine at the oldest recorded point if the history
+    // doesn't reach it.
+    pub fn reverse_continue(&mut self, address : usize) -> Option<Write> {
+        loop {
+            let write = self.history.as_ref()?.records().next_back()?.write;
+            self.step_back();
+            if let Some(write) = write.filter(|write| write.address == address) {
+                return Some(write);
+            }
+        }
+    }
+
+    // Runs until the machine halts or needs input
+    pub fn run(&mut self) -> Result<Status, Error> {
+        loop {
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
+
+    // Like run, but also stops with BudgetExceeded once the limits are
+    // reached. The instruction limit counts from the start of this call.
+    pub fn run_with(&mut self, limits : &Limits) -> Result<Status, Error> {
+        self.run_limited(limits, None)
+    }
+
+    pub fn run_traced(&mut self, limits : &Limits, trace : &mut dyn Trace) -> Result<Status, Error> {
+        self.run_limited(limits, Some(trace))
+    }
+
+    fn run_limited(&mut self, limits : &Limits, mut trace : Option<&mut dyn Trace>) -> Result<Status, Error> {
+        let mut count = 0;
+
+        if self.halted {
+            return Ok(Status::Halted);
+        }
+
+        loop {
+            if limits.instructions.is_some_and(|max| count >= max) {
+                return Ok(Status::BudgetExceeded);
+            }
+            if count % DEADLINE_CHECK_INTERVAL == 0
+                && limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(Status::BudgetExceeded);
+            }
+
+            if let Some(status) = self.step_with(trace.as_deref_mut())? {
+                return Ok(status);
+            }
+            count += 1;
+        }
+    }
+}
+
+impl Computer for Machine {
+    fn push_input(&mut self, value : Num) {
+        Machine::push_input(self, value)
+    }
+
+    fn outputs(&self) -> &[Num] {
+        Machine::outputs(self)
+    }
+
+    fn take_outputs(&mut self) -> Vec<Num> {
+        Machine::take_outputs(self)
+    }
+
+    fn run(&mut self) -> Result<Status, Error> {
+        Machine::run(self)
+    }
+
+    fn is_halted(&self) -> bool {
+        Machine::is_halted(self)
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+
+use crate::Num;
+use crate::machine::{Computer, Error, Machine};
+
+// Packets sent here go to the NAT instead of a machine
+pub const NAT_ADDRESS : Num = 255;
+
+// Machines read this when no packet is waiting for them
+pub const NO_PACKET : Num = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub from : Num,
+    pub to : Num,
+    pub x : Num,
+    pub y : Num,
+}
+
+// Everything that happens on the network, in the order it happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // From one machine to another
+    Sent(Packet),
+    // To the NAT, which only keeps the latest one
+    ToNat(Packet),
+    // The network went idle and the NAT sent its packet to address 0
+    Wake(Packet),
+    // To an address no machine has
+    Dropped(Packet),
+}
+
+// A machine on the network failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineError {
+    pub address : usize,
+    pub error : Error,
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.address, self.error)
+    }
+}
+
+impl error::Error for MachineError {}
+
+// N machines each booted with their address, sending each other packets
+// written as three outputs: destination, X and Y. The network runs in rounds,
+// giving every machine its waiting packets, or -1 if there are none, and
+// running it until it asks for more.
+pub struct Network<C : Computer = Machine> {
+    machines : Vec<C>,
+    queues : Vec<VecDeque<(Num, Num)>>,
+    // Outputs of a packet not completely written yet
+    partial : Vec<Vec<Num>>,
+    nat : Option<Packet>,
+    idle_rounds : u32,
+    // How many rounds without traffic make the network idle
+    pub idle_threshold : u32,
+    rounds : u64,
+}
+
+impl Network<Machine> {
+    // Size machines all running program
+    pub fn boot(program : &[Num], size : usize) -> Network<Machine> {
+        Network::new((0..size).map(|_| Machine::new(program.to_vec())).collect())
+    }
+}
+
+impl<C : Computer> Network<C> {
+    // Machine i gets address i
+    pub fn new(mut machines : Vec<C>) -> Network<C> {
+        for (address, machine) in machines.iter_mut().enumerate() {
+            machine.push_input(address as Num);
+        }
+        let size = machines.len();
+
+        Network {
+            machines,
+            queues : vec![VecDeque::new(); size],
+            partial : vec![Vec::new(); size],
+            nat : None,
+            idle_rounds : 0,
+            idle_threshold : 2,
+            rounds : 0,
+        }
+    }
+
+    pub fn machines(&self) -> &[C] {
+        &self.machines
+    }
+
+    // The packet the NAT will send when the network goes idle
+    pub fn nat(&self) -> Option<Packet> {
+        self.nat
+    }
+
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    // Whether every machine has halted, so nothing will happen any more
+    pub fn is_halted(&self) -> bool {
+        self.machines.iter().all(|machine| machine.is_halted())
+    }
+
+    fn route(&mut self, packet : Packet, events : &mut Vec<Event>) {
+        if packet.to == NAT_ADDRESS {
+            self.nat = Some(packet);
+            events.push(Event::ToNat(packet));
+        } else if packet.to >= 0 && (packet.to as usize) < self.queues.len() {
+            self.queues[packet.to as usize].push_back((packet.x, packet.y));
+            events.push(Event::Sent(packet));
+        } else {
+            events.push(Event::Dropped(packet));
+        }
+    }
+
+    // Runs one round, returning the packets routed during it. If nothing was
+    // waiting and nothing was sent for idle_threshold rounds in a row, the
+    // round ends with the NAT waking address 0 up.
+    pub fn step(&mut self) -> Result<Vec<Event>, MachineError> {
+        let mut events = Vec::new();
+        let mut waiting = false;
+
+        for address in 0..self.machines.len() {
+            let machine = &mut self.machines[address];
+            if machine.is_halted() {
+                continue;
+            }
+
+            if self.queues[address].is_empty() {
+                machine.push_input(NO_PACKET);
+            }
+            for (x, y) in self.queues[address].drain(..) {
+                waiting = true;
+                machine.push_input(x);
+                machine.push_input(y);
+            }
+
+            machine.run().map_err(|error| MachineError { address, error })?;
+
+            let partial = &mut self.partial[address];
+            partial.extend(machine.take_outputs());
+            let complete = partial.len() - partial.len() % 3;
+            let packets : Vec<Packet> = partial.drain(..complete).collect::<Vec<Num>>().chunks(3)
+                .map(|words| Packet { from : address as Num, to : words[0], x : words[1], y : words[2] })
+                .collect();
+            for packet in packets {
+                self.route(packet, &mut events);
+            }
+        }
+        self.rounds += 1;
+
+        if waiting || !events.is_empty() {
+            self.idle_rounds = 0;
+        } else {
+            self.idle_rounds += 1;
+        }
+
+        if self.idle_rounds >= self.idle_threshold && !self.queues.is_empty() {
+            if let Some(nat) = self.nat {
+                let packet = Packet { from : NAT_ADDRESS, to : 0, ..nat };
+                self.queues[0].push_back((packet.x, packet.y));
+                self.idle_rounds = 0;
+                events.push(Event::Wake(packet));
+            }
+        }
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,193 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::Num;
+use crate::machine::{Limits, Status};
+use crate::threaded::Threaded;
+
+// Patches can't reach past this many words, so a typo in an address can't
+// make memory grow until it runs out
+pub const ADDRESS_LIMIT : usize = 1 << 20;
+
+// A value to write into memory before the program runs, like the noun at 1
+// and verb at 2 of the gravity assist program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub address : usize,
+    pub value : Num,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    // Not ADDRESS=VALUE, or a range that isn't START..END or START..=END
+    Malformed(String),
+    // The value doesn't fit in the memory it is written to
+    OutOfRange(Patch),
+    // The address is ADDRESS_LIMIT or more
+    AddressTooLarge(usize),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Malformed(text) => write!(f, "malformed patch {:?}", text),
+            PatchError::OutOfRange(patch) => write!(f, "value {} doesn't fit at {}", patch.value, patch.address),
+            PatchError::AddressTooLarge(address) => write!(f, "address {} is past the limit of {}", address, ADDRESS_LIMIT),
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.address, self.value)
+    }
+}
+
+// Splits ADDRESS=VALUE pairs, parsing the value with parse
+fn pairs<T>(text : &str, parse : impl Fn(&str) -> Option<T>) -> Result<Vec<(usize, T)>, PatchError> {
+    text.split(',').map(str::trim).filter(|pair| !pair.is_empty()).map(|pair| {
+        let error = || PatchError::Malformed(pair.to_string());
+        let (address, value) = pair.split_once('=').ok_or_else(error)?;
+        let address : usize = address.trim().parse().map_err(|_| error())?;
+        if address >= ADDRESS_LIMIT {
+            return Err(PatchError::AddressTooLarge(address));
+        }
+        let value = parse(value.trim()).ok_or_else(error)?;
+        Ok((address, value))
+    }).collect()
+}
+
+// Parses patches written like 1=12,2=2
+pub fn parse(text : &str) -> Result<Vec<Patch>, PatchError> {
+    let pairs = pairs(text, |value| value.parse().ok())?;
+    Ok(pairs.into_iter().map(|(address, value)| Patch { address, value }).collect())
+}
+
+fn range(text : &str) -> Option<RangeInclusive<Num>> {
+    if let Some((start, end)) = text.split_once("..=") {
+        Some(start.parse().ok()?..=end.parse().ok()?)
+    } else {
+        let (start, end) = text.split_once("..")?;
+        let end : Num = end.parse().ok()?;
+        Some(start.parse().ok()?..=end.checked_sub(1)?)
+    }
+}
+
+// Parses the values to try at each address, written like 1=0..=99,2=0..100
+pub fn parse_ranges(text : &str) -> Result<Vec<(usize, RangeInclusive<Num>)>, PatchError> {
+    pairs(text, range)
+}
+
+// Writes the patches into memory, growing it if they are past the end. If
+// any patch is invalid memory is left untouched.
+pub fn apply<T>(memory : &mut Vec<T>, patches : &[Patch]) -> Result<(), PatchError>
+    where T : TryFrom<Num> + Default + Clone {
+    let values = patches.iter().map(|&patch| {
+        if patch.address >= ADDRESS_LIMIT {
+            return Err(PatchError::AddressTooLarge(patch.address));
+        }
+        T::try_from(patch.value).map_err(|_| PatchError::OutOfRange(patch))
+    }).collect::<Result<Vec<T>, PatchError>>()?;
+
+    for (patch, value) in patches.iter().zip(values) {
+        if patch.address >= memory.len() {
+            memory.resize(patch.address + 1, T::default());
+        }
+        memory[patch.address] = value;
+    }
+    Ok(())
+}
+
+// What a search looks at once the program halts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Address(usize),
+    // The last value the program printed
+    Output,
+}
+
+// Tries every combination of values over the ranges until the watched value
+// equals the target. Each run gets the inputs and is stopped after the
+// limits, runs that fail or don't halt never match.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub ranges : Vec<(usize, RangeInclusive<Num>)>,
+    pub watch : Watch,
+    pub target : Num,
+    pub inputs : Vec<Num>,
+    pub limits : Limits,
+}
+
+impl Search {
+    pub fn new(ranges : Vec<(usize, RangeInclusive<Num>)>, target : Num) -> Search {
+        Search {
+            ranges,
+            watch : Watch::Address(0),
+            target,
+            inputs : Vec::new(),
+            limits : Limits::none().instructions(1_000_000),
+        }
+    }
+
+    fn matches(&self, program : &[Num], patches : &[Patch]) -> bool {
+        let mut memory = program.to_vec();
+        if apply(&mut memory, patches).is_err() {
+            return false;
+        }
+
+        let mut machine = Threaded::new(memory);
+        for &input in &self.inputs {
+            machine.push_input(input);
+        }
+        if machine.run_with(&self.limits) != Ok(Status::Halted) {
+            return false;
+        }
+
+        let value = match self.watch {
+            Watch::Address(address) => machine.memory().get(address).copied().unwrap_or(0),
+            Watch::Output => match machine.outputs().last() {
+                Some(&value) => value,
+                None => return false,
+            },
+        };
+        value == self.target
+    }
+
+    // The first patches that match, trying the last range fastest, and how
+    // many runs it took
+    pub fn run(&self, program : &[Num]) -> (Option<Vec<Patch>>, u64) {
+        let mut patches : Vec<Patch> = self.ranges.iter()
+            .map(|(address, range)| Patch { address : *address, value : *range.start() })
+            .collect();
+        if self.ranges.iter().any(|(_, range)| range.is_empty()) {
+            return (None, 0);
+        }
+
+        let mut runs = 0;
+        loop {
+            runs += 1;
+            if self.matches(program, &patches) {
+                return (Some(patches), runs);
+            }
+
+            // Counts up like an odometer, carrying into the earlier ranges
+            let mut index = patches.len();
+            loop {
+                if index == 0 {
+                    return (None, runs);
+                }
+                index -= 1;
+                let range = &self.ranges[index].1;
+                if patches[index].value < *range.end() {
+                    patches[index].value += 1;
+                    break;
+                }
+                patches[index].value = *range.start();
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::Num;
+use crate::disasm;
+use crate::machine::{Instruction, Machine, Trace};
+
+// Counts executions per pc and per opcode and the time spent waiting for
+// input. Call frames are inferred from the relative base: compiled intcode
+// grows it when entering a function and shrinks it by the same amount when
+// leaving, so every increase opens a frame named after the pc that did it.
+#[derive(Debug)]
+pub struct Profiler {
+    pc_counts : Vec<u64>,
+    opcode_counts : BTreeMap<Num, u64>,
+    total : u64,
+    blocked : Duration,
+    waits : u64,
+    waiting_since : Option<Instant>,
+    // Open frames as (pc that opened them, relative base growth)
+    frames : Vec<(usize, Num)>,
+    // Stacks are interned so counting doesn't allocate on every instruction
+    stack_ids : HashMap<Vec<usize>, usize>,
+    stack_counts : Vec<(Vec<usize>, u64)>,
+    current_stack : usize,
+    last_pc : usize,
+    last_relative_base : Num,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        let mut profiler = Profiler {
+            pc_counts : Vec::new(),
+            opcode_counts : BTreeMap::new(),
+            total : 0,
+            blocked : Duration::default(),
+            waits : 0,
+            waiting_since : None,
+            frames : Vec::new(),
+            stack_ids : HashMap::new(),
+            stack_counts : Vec::new(),
+            current_stack : 0,
+            last_pc : 0,
+            last_relative_base : 0,
+        };
+        profiler.current_stack = profiler.intern();
+        profiler
+    }
+
+    fn intern(&mut self) -> usize {
+        let stack : Vec<usize> = self.frames.iter().map(|&(pc, _)| pc).collect();
+        let stack_counts = &mut self.stack_counts;
+        *self.stack_ids.entry(stack.clone()).or_insert_with(|| {
+            stack_counts.push((stack, 0));
+            stack_counts.len() - 1
+        })
+    }
+
+    // Opens or closes frames after the relative base changed by delta
+    fn adjust_frames(&mut self, delta : Num) {
+        if delta > 0 {
+            self.frames.push((self.last_pc, delta));
+        } else {
+            let mut remaining = -delta;
+            while remaining > 0 {
+                match self.frames.pop() {
+                    Some((pc, growth)) if growth > remaining => {
+                        // Only part of the frame was released, keep the rest
+                        self.frames.push((pc, growth - remaining));
+                        remaining = 0;
+                    },
+                    Some((_, growth)) => remaining -= growth,
+                    None => remaining = 0,
+                }
+            }
+        }
+        self.current_stack = self.intern();
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count_at(&self, pc : usize) -> u64 {
+        self.pc_counts.get(pc).copied().unwrap_or(0)
+    }
+
+    pub fn opcode_counts(&self) -> &BTreeMap<Num, u64> {
+        &self.opcode_counts
+    }
+
+    pub fn blocked(&self) -> Duration {
+        self.blocked
+    }
+
+    // Runs of adjacent executed instructions, hottest first, as
+    // (first pc, end pc, executions)
+    pub fn hot_ranges(&self, memory : &[Num]) -> Vec<(usize, usize, u64)> {
+        let mut ranges : Vec<(usize, usize, u64)> = Vec::new();
+
+        for (pc, &count) in self.pc_counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let (_, length) = disasm::instruction_at(memory, pc);
+            match ranges.last_mut() {
+                Some(range) if range.1 == pc => {
+                    range.1 = pc + length;
+                    range.2 += count;
+                },
+                _ => ranges.push((pc, pc + length, count)),
+            }
+        }
+
+        ranges.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        ranges
+    }
+
+    // A readable summary: totals, opcode mix and the hottest ranges with
+    // their disassembly. The memory should be the program being profiled.
+    pub fn report(&self, memory : &[Num], top : usize) -> String {
+        let mut out = String::new();
+        let percent = |count : u64| 100.0 * count as f64 / self.total.max(1) as f64;
+
+        writeln!(out, "Executed {} instructions", self.total).unwrap();
+        writeln!(out, "Blocked on input for {:.3}s over {} waits", self.blocked.as_secs_f64(), self.waits).unwrap();
+
+        writeln!(out, "\nOpcodes:").unwrap();
+        let mut opcodes : Vec<(&Num, &u64)> = self.opcode_counts.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (&opcode, &count) in opcodes {
+            let name = disasm::mnemonic(opcode).unwrap_or("?");
+            writeln!(out, "  {:<5} {:>12} {:>6.2}%", name, count, percent(count)).unwrap();
+        }
+
+        writeln!(out, "\nHottest ranges:").unwrap();
+        for (start, end, count) in self.hot_ranges(memory).into_iter().take(top) {
+            writeln!(out, "  {}..{}  {} executions ({:.2}%)", start, end, count, percent(count)).unwrap();
+            for (pc, text) in disasm::disassemble(memory, start, end) {
+                writeln!(out, "    {:>6} {:>12}  {}", pc, self.count_at(pc), text).unwrap();
+            }
+        }
+
+        out
+    }
+
+    // One line per call stack with the instructions executed in it, in the
+    // folded format flamegraph.pl and inferno read
+    pub fn folded(&self) -> String {
+        let mut lines : Vec<String> = self.stack_counts.iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(stack, count)| {
+                let mut line = String::from("main");
+                for pc in stack {
+                    write!(line, ";fn@{}", pc).unwrap();
+                }
+                format!("{} {}", line, count)
+            })
+            .collect();
+        lines.sort();
+
+        let mut out = lines.join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+impl Trace for Profiler {
+    fn instruction(&mut self, machine : &Machine, instruction : Instruction) {
+        if let Some(since) = self.waiting_since.take() {
+            self.blocked += since.elapsed();
+        }
+
+        // The relative base only changes once the previous instruction ran
+        let relative_base = machine.relative_base();
+        if relative_base != self.last_relative_base {
+            self.adjust_frames(relative_base - self.last_relative_base);
+            self.last_relative_base = relative_base;
+        }
+
+        let pc = machine.pc();
+        if pc >= self.pc_counts.len() {
+            self.pc_counts.resize(pc + 1, 0);
+        }
+        self.pc_counts[pc] += 1;
+        *self.opcode_counts.entry(instruction.opcode).or_insert(0) += 1;
+        self.stack_counts[self.current_stack].1 += 1;
+        self.total += 1;
+        self.last_pc = pc;
+    }
+
+    fn needs_input(&mut self, _machine : &Machine) {
+        if self.waiting_since.is_none() {
+            self.waiting_since = Some(Instant::now());
+            self.waits += 1;
+        }
+    }
+
+    fn blocked(&mut self, _machine : &Machine, waited : Duration) {
+        self.blocked += waited;
+        self.waits += 1;
+    }
+}
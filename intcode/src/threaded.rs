@@ -0,0 +1,340 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::Num;
+use crate::disasm;
+use crate::machine::{decode, Computer, Error, Limits, Status, DEADLINE_CHECK_INTERVAL};
+
+// An operand with its mode already applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Position(usize),
+    Immediate(Num),
+    Relative(Num),
+    // Position operands pointing below zero and unknown modes only fail
+    // once they are used, like they do on the machine
+    Negative(Num),
+    Invalid(Num),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, Operand),
+    JumpIfFalse(Operand, Operand),
+    AdjustBase(Operand),
+    Halt,
+    Unknown(Num),
+}
+
+impl Op {
+    fn length(self) -> usize {
+        match self {
+            Op::Add(..) | Op::Multiply(..) | Op::LessThan(..) | Op::Equals(..) => 4,
+            Op::JumpIfTrue(..) | Op::JumpIfFalse(..) => 3,
+            Op::Input(_) | Op::Output(_) | Op::AdjustBase(_) => 2,
+            Op::Halt | Op::Unknown(_) => 1,
+        }
+    }
+}
+
+// A faster engine with the same behaviour as machine::Machine, without the
+// tracing. Each instruction is decoded once, the first time it runs, into an
+// op whose operands are resolved to the address or value they stand for, and
+// the run loop dispatches on those ops with the pc kept in a local. Writes
+// into decoded instructions throw the affected ops away, so self modifying
+// programs still work.
+#[derive(Debug, Clone)]
+pub struct Threaded {
+    memory : Vec<Num>,
+    // Decoded ops by the pc they start at
+    ops : Vec<Option<Op>>,
+    // Words belonging to a decoded op
+    code : Vec<bool>,
+    pc : usize,
+    relative_base : Num,
+    inputs : VecDeque<Num>,
+    outputs : Vec<Num>,
+    halted : bool,
+    executed : u64,
+}
+
+impl Threaded {
+    pub fn new(program : Vec<Num>) -> Threaded {
+        Threaded {
+            ops : Vec::new(),
+            code : Vec::new(),
+            memory : program,
+            pc : 0,
+            relative_base : 0,
+            inputs : VecDeque::new(),
+            outputs : Vec::new(),
+            halted : false,
+            executed : 0,
+        }
+    }
+
+    pub fn push_input(&mut self, value : Num) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn outputs(&self) -> &[Num] {
+        &self.outputs
+    }
+
+    pub fn take_outputs(&mut self) -> Vec<Num> {
+        std::mem::take(&mut self.outputs)
+    }
+
+    pub fn memory(&self) -> &[Num] {
+        &self.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Num {
+        self.relative_base
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    fn grow(&mut self, length : usize) {
+        if length > self.memory.len() {
+            self.memory.resize(length, 0);
+        }
+    }
+
+    fn operand(&self, position : usize, mode : Num) -> Operand {
+        let value = self.memory.get(position).copied().unwrap_or(0);
+        match mode {
+            0 if value < 0 => Operand::Negative(value),
+            0 => Operand::Position(value as usize),
+            1 => Operand::Immediate(value),
+            2 => Operand::Relative(value),
+            _ => Operand::Invalid(mode),
+        }
+    }
+
+    fn decode_at(&mut self, pc : usize) -> Op {
+        let instruction = decode(self.memory.get(pc).copied().unwrap_or(0));
+        let length = 1 + disasm::parameters(instruction.opcode);
+        self.grow(pc + length);
+
+        let (a, b, c) = (
+            self.operand(pc + 1, instruction.mode1),
+            self.operand(pc + 2, instruction.mode2),
+            self.operand(pc + 3, instruction.mode3),
+        );
+        let op = match instruction.opcode {
+            1 => Op::Add(a, b, c),
+            2 => Op::Multiply(a, b, c),
+            3 => Op::Input(a),
+            4 => Op::Output(a),
+            5 => Op::JumpIfTrue(a, b),
+            6 => Op::JumpIfFalse(a, b),
+            7 => Op::LessThan(a, b, c),
+            8 => Op::Equals(a, b, c),
+            9 => Op::AdjustBase(a),
+            99 => Op::Halt,
+            opcode => Op::Unknown(opcode),
+        };
+
+        if pc >= self.ops.len() {
+            self.ops.resize(pc + 1, None);
+        }
+        self.ops[pc] = Some(op);
+        if pc + length > self.code.len() {
+            self.code.resize(pc + length, false);
+        }
+        for word in &mut self.code[pc..pc + length] {
+            *word = true;
+        }
+        op
+    }
+
+    fn address(&mut self, pc : usize, operand : Operand) -> Result<usize, Error> {
+        let address = match operand {
+            Operand::Position(address) => address,
+            Operand::Relative(offset) => {
+                let address = offset.wrapping_add(self.relative_base);
+                if address < 0 {
+                    return Err(Error::NegativeAddress { pc, address });
+                }
+                address as usize
+            },
+            Operand::Negative(address) => return Err(Error::NegativeAddress { pc, address }),
+            Operand::Immediate(_) => return Err(Error::InvalidMode { pc, mode : 1 }),
+            Operand::Invalid(mode) => return Err(Error::InvalidMode { pc, mode }),
+        };
+        self.grow(address + 1);
+        Ok(address)
+    }
+
+    #[inline(always)]
+    fn read(&mut self, pc : usize, operand : Operand) -> Result<Num, Error> {
+        match operand {
+            Operand::Immediate(value) => Ok(value),
+            Operand::Position(address) if address < self.memory.len() => Ok(self.memory[address]),
+            operand => {
+                let address = self.address(pc, operand)?;
+                Ok(self.memory[address])
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn write(&mut self, pc : usize, operand : Operand, value : Num) -> Result<(), Error> {
+        let address = self.address(pc, operand)?;
+        self.memory[address] = value;
+
+        if self.code.get(address).copied().unwrap_or(false) {
+            let first = address.saturating_sub(3);
+            for entry in self.ops.iter_mut().take(address + 1).skip(first) {
+                *entry = None;
+            }
+        }
+        Ok(())
+    }
+
+    // Executes a single instruction, like Machine::step
+    pub fn step(&mut self) -> Result<Option<Status>, Error> {
+        match self.run_with(&Limits::none().instructions(1))? {
+            Status::BudgetExceeded => Ok(None),
+            status => Ok(Some(status)),
+        }
+    }
+
+    // Runs until the machine halts or needs input
+    pub fn run(&mut self) -> Result<Status, Error> {
+        self.run_with(&Limits::none())
+    }
+
+    // Like Machine::run_with: stops with BudgetExceeded once the limits are
+    // reached, counting instructions from the start of this call
+    pub fn run_with(&mut self, limits : &Limits) -> Result<Status, Error> {
+        if self.halted {
+            return Ok(Status::Halted);
+        }
+
+        let budget = limits.instructions.unwrap_or(u64::MAX);
+        let mut count = 0;
+        let mut pc = self.pc;
+
+        let result = loop {
+            if count >= budget {
+                break Ok(Status::BudgetExceeded);
+            }
+            if count % DEADLINE_CHECK_INTERVAL == 0
+                && limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break Ok(Status::BudgetExceeded);
+            }
+
+            let op = match self.ops.get(pc) {
+                Some(Some(op)) => *op,
+                _ => self.decode_at(pc),
+            };
+
+            let next = match self.execute(pc, op) {
+                Ok(Some(next)) => next,
+                Ok(None) => break Ok(if self.halted { Status::Halted } else { Status::NeedsInput }),
+                Err(e) => break Err(e),
+            };
+            count += 1;
+            pc = next;
+        };
+
+        self.executed += count;
+        self.pc = pc;
+        result
+    }
+
+    // Executes op and returns where to go next, or None when the machine
+    // stops at pc
+    #[inline(always)]
+    fn execute(&mut self, pc : usize, op : Op) -> Result<Option<usize>, Error> {
+        let next = pc + op.length();
+        match op {
+            Op::Add(a, b, c) => {
+                let value = self.read(pc, a)?.wrapping_add(self.read(pc, b)?);
+                self.write(pc, c, value)?;
+            },
+            Op::Multiply(a, b, c) => {
+                let value = self.read(pc, a)?.wrapping_mul(self.read(pc, b)?);
+                self.write(pc, c, value)?;
+            },
+            Op::LessThan(a, b, c) => {
+                let value = (self.read(pc, a)? < self.read(pc, b)?) as Num;
+                self.write(pc, c, value)?;
+            },
+            Op::Equals(a, b, c) => {
+                let value = (self.read(pc, a)? == self.read(pc, b)?) as Num;
+                self.write(pc, c, value)?;
+            },
+            Op::Input(a) => match self.inputs.pop_front() {
+                Some(value) => self.write(pc, a, value)?,
+                None => return Ok(None),
+            },
+            Op::Output(a) => {
+                let value = self.read(pc, a)?;
+                self.outputs.push(value);
+            },
+            Op::JumpIfTrue(a, b) | Op::JumpIfFalse(a, b) => {
+                let condition = self.read(pc, a)? != 0;
+                let target = self.read(pc, b)?;
+                if condition == matches!(op, Op::JumpIfTrue(..)) {
+                    if target < 0 {
+                        return Err(Error::NegativeAddress { pc, address : target });
+                    }
+                    return Ok(Some(target as usize));
+                }
+            },
+            Op::AdjustBase(a) => {
+                let value = self.read(pc, a)?;
+                self.relative_base = self.relative_base.wrapping_add(value);
+            },
+            Op::Halt => {
+                // The halt counts as executed but the loop won't count it
+                self.executed += 1;
+                self.halted = true;
+                return Ok(None);
+            },
+            Op::Unknown(opcode) => return Err(Error::UnknownOpcode { pc, opcode }),
+        }
+        Ok(Some(next))
+    }
+}
+
+impl Computer for Threaded {
+    fn push_input(&mut self, value : Num) {
+        Threaded::push_input(self, value)
+    }
+
+    fn outputs(&self) -> &[Num] {
+        Threaded::outputs(self)
+    }
+
+    fn take_outputs(&mut self) -> Vec<Num> {
+        Threaded::take_outputs(self)
+    }
+
+    fn run(&mut self) -> Result<Status, Error> {
+        Threaded::run(self)
+    }
+
+    fn is_halted(&self) -> bool {
+        Threaded::is_halted(self)
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::BTreeSet;
+
+use crate::Num;
+use crate::cfg::Cfg;
+use crate::disasm;
+use crate::machine::{decode, Instruction};
+
+const VALUES_PER_LINE : usize = 16;
+
+fn word(memory : &[Num], position : usize) -> Num {
+    memory.get(position).copied().unwrap_or(0)
+}
+
+// How an operand is read in the generated code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Position(usize),
+    Immediate(Num),
+    Relative(Num),
+    // The program writes this word itself, so it is read when the
+    // instruction runs
+    Dynamic(usize, Num),
+}
+
+struct Transpiler<'a> {
+    memory : &'a [Num],
+    cfg : Cfg,
+    // Operand words the program is known to write to
+    dynamic : BTreeSet<usize>,
+    // Words compiled into the generated code as constants
+    code : BTreeSet<usize>,
+    out : String,
+}
+
+impl<'a> Transpiler<'a> {
+    fn new(memory : &'a [Num]) -> Transpiler<'a> {
+        let cfg = Cfg::build(memory);
+        let dynamic = cfg.self_modifying.iter().map(|write| write.address).collect();
+        let mut transpiler = Transpiler { memory, cfg, dynamic, code : BTreeSet::new(), out : String::new() };
+
+        let compiled : Vec<usize> = transpiler.cfg.blocks.values()
+            .flat_map(|block| block.instructions.iter().copied())
+            .filter(|&pc| transpiler.compiles(pc))
+            .collect();
+        let mut code = BTreeSet::new();
+        for pc in compiled {
+            let length = 1 + disasm::parameters(transpiler.instruction(pc).opcode);
+            code.extend((pc..pc + length).filter(|position| *position == pc || !transpiler.dynamic.contains(position)));
+        }
+        transpiler.code = code;
+        transpiler
+    }
+
+    fn instruction(&self, pc : usize) -> Instruction {
+        decode(word(self.memory, pc))
+    }
+
+    fn operand(&self, position : usize, mode : Num) -> Option<Operand> {
+        let value = word(self.memory, position);
+        match mode {
+            _ if self.dynamic.contains(&position) && mode <= 2 => Some(Operand::Dynamic(position, mode)),
+            0 if value >= 0 => Some(Operand::Position(value as usize)),
+            1 => Some(Operand::Immediate(value)),
+            2 => Some(Operand::Relative(value)),
+            _ => None,
+        }
+    }
+
+    // Instructions that would fail, or whose opcode the program overwrites,
+    // are left to the interpreter
+    fn compiles(&self, pc : usize) -> bool {
+        let instruction = self.instruction(pc);
+        let count = disasm::parameters(instruction.opcode);
+        let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+        let writes = match instruction.opcode {
+            1 | 2 | 7 | 8 => Some(2),
+            3 => Some(0),
+            _ => None,
+        };
+
+        disasm::mnemonic(instruction.opcode).is_some()
+            && word(self.memory, pc) >= 0
+            && pc + count < self.memory.len()
+            && !self.dynamic.contains(&pc)
+            && (0..count).all(|i| match self.operand(pc + 1 + i, modes[i]) {
+                None => false,
+                Some(Operand::Immediate(_)) => writes != Some(i),
+                Some(_) => true,
+            })
+    }
+
+    fn line(&mut self, indent : usize, text : &str) {
+        for _ in 0..indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn read(&self, operand : Operand, pc : usize) -> String {
+        match operand {
+            Operand::Position(address) if address < self.memory.len() => format!("self.state.memory[{}]", address),
+            Operand::Position(address) => format!("self.get({})", address),
+            Operand::Immediate(value) => format!("{}", value),
+            Operand::Relative(offset) => format!("self.get(self.relative({}, {})?)", offset, pc),
+            Operand::Dynamic(position, 0) => format!("self.get(address(self.state.memory[{}], {})?)", position, pc),
+            Operand::Dynamic(position, 1) => format!("self.state.memory[{}]", position),
+            Operand::Dynamic(position, _) => format!("self.get(self.relative(self.state.memory[{}], {})?)", position, pc),
+        }
+    }
+
+    // Stores value, handing over to the interpreter if that changed code
+    fn write(&mut self, indent : usize, operand : Operand, pc : usize, next : usize) {
+        let address = match operand {
+            Operand::Position(address) if address < self.memory.len() && !self.code.contains(&address) => {
+                self.line(indent, &format!("self.state.memory[{}] = value;", address));
+                return;
+            },
+            Operand::Position(address) if !self.code.contains(&address) => {
+                self.line(indent, &format!("self.store({}, value);", address));
+                return;
+            },
+            Operand::Position(address) => format!("{}", address),
+            Operand::Relative(offset) => format!("self.relative({}, {})?", offset, pc),
+            Operand::Dynamic(position, 0) => format!("address(self.state.memory[{}], {})?", position, pc),
+            Operand::Dynamic(position, _) => format!("self.relative(self.state.memory[{}], {})?", position, pc),
+            Operand::Immediate(_) => unreachable!("immediate writes are interpreted"),
+        };
+        self.line(indent, &format!("self.store({}, value);", address));
+        self.line(indent, "if self.modified {");
+        self.line(indent + 1, &format!("self.state.pc = {};", next));
+        self.line(indent + 1, "return self.interpret();");
+        self.line(indent, "}");
+    }
+
+    // Emits the instruction at pc, returning false when it ends the arm
+    fn emit_instruction(&mut self, indent : usize, pc : usize) -> bool {
+        let instruction = self.instruction(pc);
+        let modes = [instruction.mode1, instruction.mode2, instruction.mode3];
+        let count = disasm::parameters(instruction.opcode);
+        let operands : Vec<Operand> = (0..count).map(|i| self.operand(pc + 1 + i, modes[i]).unwrap()).collect();
+        let next = pc + 1 + count;
+
+        self.line(indent, &format!("// {}: {}", pc, disasm::instruction_at(self.memory, pc).0));
+        match instruction.opcode {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (self.read(operands[0], pc), self.read(operands[1], pc));
+                self.line(indent, &format!("let a : Num = {};", a));
+                self.line(indent, &format!("let b : Num = {};", b));
+                self.line(indent, match instruction.opcode {
+                    1 => "let value = a.wrapping_add(b);",
+                    2 => "let value = a.wrapping_mul(b);",
+                    7 => "let value = (a < b) as Num;",
+                    _ => "let value = (a == b) as Num;",
+                });
+                self.write(indent, operands[2], pc, next);
+            },
+            3 => {
+                self.line(indent, "let value = match self.state.inputs.pop_front() {");
+                self.line(indent + 1, "Some(value) => value,");
+                self.line(indent + 1, "None => {");
+                self.line(indent + 2, &format!("self.state.pc = {};", pc));
+                self.line(indent + 2, "return Ok(Status::NeedsInput);");
+                self.line(indent + 1, "},");
+                self.line(indent, "};");
+                self.write(indent, operands[0], pc, next);
+            },
+            4 => {
+                let value = self.read(operands[0], pc);
+                self.line(indent, &format!("let value : Num = {};", value));
+                self.line(indent, "self.state.outputs.push(value);");
+            },
+            5 | 6 => {
+                let test = if instruction.opcode == 5 { "!=" } else { "==" };
+                // Both operands are read whichever way the jump goes
+                let condition = match operands[0] {
+                    Operand::Immediate(condition) => Err((condition != 0) == (instruction.opcode == 5)),
+                    operand => Ok(self.read(operand, pc)),
+                };
+                if let Ok(condition) = &condition {
+                    self.line(indent, &format!("let condition : Num = {};", condition));
+                }
+                let target = match operands[1] {
+                    Operand::Immediate(target) if target >= 0 => format!("{}", target),
+                    operand => {
+                        let value = self.read(operand, pc);
+                        self.line(indent, &format!("let target : Num = {};", value));
+                        format!("address(target, {})?", pc)
+                    },
+                };
+                match condition {
+                    Err(true) => self.line(indent, &format!("self.state.pc = {};", target)),
+                    Err(false) => self.line(indent, &format!("self.state.pc = {};", next)),
+                    Ok(_) => self.line(indent, &format!("self.state.pc = if condition {} 0 {{ {} }} else {{ {} }};", test, target, next)),
+                }
+                return false;
+            },
+            9 => {
+                let value = self.read(operands[0], pc);
+                self.line(indent, &format!("let value : Num = {};", value));
+                self.line(indent, "self.state.relative_base = self.state.relative_base.wrapping_add(value);");
+            },
+            _ => {
+                self.line(indent, "self.state.halted = true;");
+                self.line(indent, &format!("self.state.pc = {};", pc));
+                self.line(indent, "return Ok(Status::Halted);");
+                return false;
+            },
+        }
+        true
+    }
+
+    // One match arm per run of compiled instructions. Arms end at jumps, at
+    // the end of a block and before instructions left to the interpreter.
+    fn emit_arms(&mut self, indent : usize) {
+        let blocks : Vec<Vec<usize>> = self.cfg.blocks.values().map(|block| block.instructions.clone()).collect();
+
+        for instructions in blocks {
+            let mut arm_open = false;
+            let mut next = 0;
+            for pc in instructions {
+                if !self.compiles(pc) {
+                    if arm_open {
+                        self.line(indent + 1, &format!("self.state.pc = {};", pc));
+                        self.line(indent, "},");
+                        arm_open = false;
+                    }
+                    // The fallback arm runs it
+                    next = 0;
+                    continue;
+                }
+                if !arm_open {
+                    self.line(indent, &format!("{} => {{", pc));
+                    arm_open = true;
+                }
+                next = pc + 1 + disasm::parameters(self.instruction(pc).opcode);
+                if !self.emit_instruction(indent + 1, pc) {
+                    self.line(indent, "},");
+                    arm_open = false;
+                }
+            }
+            if arm_open {
+                self.line(indent + 1, &format!("self.state.pc = {};", next));
+                self.line(indent, "},");
+            }
+        }
+    }
+
+    // Compiled words as ranges for a match pattern
+    fn code_ranges(&self) -> String {
+        let mut ranges : Vec<(usize, usize)> = Vec::new();
+        for &address in &self.code {
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == address => range.1 = address,
+                _ => ranges.push((address, address)),
+            }
+        }
+        if ranges.is_empty() {
+            return String::from("false");
+        }
+        let patterns : Vec<String> = ranges.iter().map(|&(first, last)| {
+            if first == last { format!("{}", first) } else { format!("{}..={}", first, last) }
+        }).collect();
+        format!("matches!(address, {})", patterns.join(" | "))
+    }
+
+    fn emit(&mut self) {
+        self.line(0, "// Generated by intcode-transpile, edit the intcode program instead");
+        self.line(0, "");
+        self.line(0, "use intcode::Num;");
+        self.line(0, "use intcode::machine::{decode, Computer, Error, Machine, State, Status};");
+        self.line(0, "");
+
+        self.line(0, "const PROGRAM : &[Num] = &[");
+        let values : Vec<String> = self.memory.iter().map(|value| value.to_string()).collect();
+        for chunk in values.chunks(VALUES_PER_LINE) {
+            self.line(1, &format!("{},", chunk.join(", ")));
+        }
+        self.line(0, "];");
+        self.line(0, "");
+
+        self.line(0, "// Words the compiled code has as constants");
+        self.line(0, "fn is_code(address : usize) -> bool {");
+        let ranges = self.code_ranges();
+        self.line(1, &ranges);
+        self.line(0, "}");
+        self.line(0, "");
+
+        self.out.push_str(PRELUDE);
+
+        self.line(1, "fn execute(&mut self) -> Result<Status, Error> {");
+        self.line(2, "loop {");
+        self.line(3, "match self.state.pc {");
+        self.emit_arms(4);
+        self.line(4, "_ => {");
+        self.line(5, "if let Some(status) = self.step()? {");
+        self.line(6, "return Ok(status);");
+        self.line(5, "}");
+        self.line(5, "if self.modified {");
+        self.line(6, "return self.interpret();");
+        self.line(5, "}");
+        self.line(4, "},");
+        self.line(3, "}");
+        self.line(2, "}");
+        self.line(1, "}");
+        self.line(0, "}");
+
+        self.out.push_str(COMPUTER);
+    }
+}
+
+const PRELUDE : &str = "\
+fn address(value : Num, pc : usize) -> Result<usize, Error> {
+    if value < 0 {
+        Err(Error::NegativeAddress { pc, address : value })
+    } else {
+        Ok(value as usize)
+    }
+}
+
+fn failed_at(error : &Error) -> usize {
+    match *error {
+        Error::UnknownOpcode { pc, .. } | Error::InvalidMode { pc, .. } | Error::NegativeAddress { pc, .. }
+        | Error::NoInput { pc } => pc,
+    }
+}
+
+pub struct Program {
+    state : State,
+    // Set once the program changes compiled code, from then on everything
+    // is interpreted
+    modified : bool,
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program {
+            state : State { memory : PROGRAM.to_vec(), ..State::default() },
+            modified : false,
+        }
+    }
+
+    pub fn memory(&self) -> &[Num] {
+        &self.state.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.state.pc
+    }
+
+    pub fn is_interpreting(&self) -> bool {
+        self.modified
+    }
+
+    // Changes memory before running, for instance to patch the program
+    pub fn poke(&mut self, address : usize, value : Num) {
+        self.store(address, value);
+    }
+
+    fn get(&mut self, address : usize) -> Num {
+        if address >= self.state.memory.len() {
+            self.state.memory.resize(address + 1, 0);
+        }
+        self.state.memory[address]
+    }
+
+    fn store(&mut self, address : usize, value : Num) {
+        if address >= self.state.memory.len() {
+            self.state.memory.resize(address + 1, 0);
+        }
+        self.state.memory[address] = value;
+        if is_code(address) && value != PROGRAM[address] {
+            self.modified = true;
+        }
+    }
+
+    fn relative(&self, offset : Num, pc : usize) -> Result<usize, Error> {
+        address(offset.wrapping_add(self.state.relative_base), pc)
+    }
+
+    fn interpret(&mut self) -> Result<Status, Error> {
+        let mut machine = Machine::from_state(std::mem::take(&mut self.state));
+        let result = machine.run();
+        self.state = machine.into_state();
+        result
+    }
+
+    // Runs a single instruction on the interpreter
+    fn step(&mut self) -> Result<Option<Status>, Error> {
+        let mut machine = Machine::from_state(std::mem::take(&mut self.state));
+        let pc = machine.pc();
+        let instruction = decode(machine.peek(pc));
+        let written = match instruction.opcode {
+            1 | 2 | 7 | 8 => machine.operand_address(pc + 3, instruction.mode3),
+            3 => machine.operand_address(pc + 1, instruction.mode1),
+            _ => None,
+        };
+
+        let result = machine.step();
+        self.state = machine.into_state();
+        if let Some(address) = written {
+            if is_code(address) && self.state.memory[address] != PROGRAM[address] {
+                self.modified = true;
+            }
+        }
+        result
+    }
+
+";
+
+const COMPUTER : &str = "
+impl Default for Program {
+    fn default() -> Program {
+        Program::new()
+    }
+}
+
+impl Computer for Program {
+    fn push_input(&mut self, value : Num) {
+        self.state.inputs.push_back(value);
+    }
+
+    fn outputs(&self) -> &[Num] {
+        &self.state.outputs
+    }
+
+    fn take_outputs(&mut self) -> Vec<Num> {
+        std::mem::take(&mut self.state.outputs)
+    }
+
+    fn run(&mut self) -> Result<Status, Error> {
+        if self.state.halted {
+            return Ok(Status::Halted);
+        }
+        if self.modified {
+            return self.interpret();
+        }
+        self.execute().map_err(|error| {
+            self.state.pc = failed_at(&error);
+            error
+        })
+    }
+
+    fn is_halted(&self) -> bool {
+        self.state.halted
+    }
+}
+";
+
+// Rust source for a module running memory as compiled code. It has a
+// Program type implementing machine::Computer, so it can stand in for a
+// Machine. Each basic block becomes a match arm on the pc, so indirect jumps
+// go through the match. Words the program is seen writing to statically are
+// read at run time, anything else overwriting compiled code makes it switch
+// to the interpreter for good.
+pub fn transpile(memory : &[Num]) -> String {
+    let mut transpiler = Transpiler::new(memory);
+    transpiler.emit();
+    transpiler.out
+}
@@ -0,0 +1,100 @@
+use intcode::Num;
+use intcode::binary::{self, BinaryError, MAGIC, VERSION};
+use intcode::loader::{self, LoadError};
+
+#[test]
+fn round_trips() {
+    let programs : [&[Num]; 3] = [
+        &[1, 0, 0, 3, 99],
+        &[-1, 63, -64, 64, -65, 1 << 40, -(1 << 40)],
+        &[Num::MAX, Num::MIN, Num::MAX - 1, Num::MIN + 1],
+    ];
+    for program in &programs {
+        let bytes = binary::encode(program);
+        assert!(binary::is_binary(&bytes));
+        assert_eq!(binary::decode(&bytes).as_deref(), Ok(*program));
+    }
+}
+
+#[test]
+fn encodes_small_words_in_a_byte() {
+    let bytes = binary::encode(&[1, -1, 0]);
+    // Zigzag encoding maps 1, -1 and 0 to 2, 1 and 0
+    assert_eq!(&bytes[..bytes.len() - 4], b"ICB\x01\x03\x02\x01\x00");
+}
+
+#[test]
+fn rejects_other_files() {
+    assert_eq!(binary::decode(b"1,2,3"), Err(BinaryError::BadMagic));
+    assert_eq!(binary::decode(b"IC"), Err(BinaryError::BadMagic));
+    assert_eq!(binary::decode(b"ICB"), Err(BinaryError::Truncated { offset : 3 }));
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut bytes = binary::encode(&[1, 2]);
+    bytes[MAGIC.len()] = VERSION + 1;
+    assert_eq!(binary::decode(&bytes), Err(BinaryError::UnsupportedVersion(VERSION + 1)));
+}
+
+#[test]
+fn detects_corruption() {
+    let mut bytes = binary::encode(&[1, 2, 3]);
+    bytes[5] ^= 0x04;
+    assert!(matches!(binary::decode(&bytes), Err(BinaryError::Checksum { .. })));
+
+    let mut bytes = binary::encode(&[1, 2, 3]);
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0x01;
+    assert!(matches!(binary::decode(&bytes), Err(BinaryError::Checksum { .. })));
+}
+
+#[test]
+fn rejects_truncated_varints() {
+    // A length of one followed by a word whose continuation bit is set
+    assert_eq!(binary::decode(b"ICB\x01\x01\x80"), Err(BinaryError::Truncated { offset : 6 }));
+    // A length that never ends
+    assert_eq!(binary::decode(b"ICB\x01\xff\xff"), Err(BinaryError::Truncated { offset : 6 }));
+    // Every word is there but the checksum isn't
+    let bytes = binary::encode(&[1, 2]);
+    let cut = bytes.len() - 2;
+    assert_eq!(binary::decode(&bytes[..cut]), Err(BinaryError::Truncated { offset : cut - 2 }));
+}
+
+#[test]
+fn rejects_empty_programs_like_the_text_format() {
+    let bytes = binary::encode(&[]);
+    assert_eq!(binary::decode(&bytes), Err(BinaryError::EmptyProgram));
+
+    let binary = loader::load_bytes::<Num>(bytes).unwrap_err();
+    let text = loader::load_bytes::<Num>(b"# nothing here\n".to_vec()).unwrap_err();
+    assert!(matches!(binary, LoadError::Binary(BinaryError::EmptyProgram)), "{:?}", binary);
+    assert!(text.to_string().contains("empty program"), "{}", text);
+    assert_eq!(binary.to_string(), "empty program");
+}
+
+#[test]
+fn rejects_varints_past_64_bits() {
+    let mut bytes = b"ICB\x01\x01".to_vec();
+    bytes.extend_from_slice(&[0xff; 9]);
+    bytes.push(0x02);
+    assert_eq!(binary::decode(&bytes), Err(BinaryError::Overflow { offset : 5 }));
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    let mut bytes = binary::encode(&[1, 2]);
+    let end = bytes.len();
+    bytes.push(0);
+    assert_eq!(binary::decode(&bytes), Err(BinaryError::TrailingBytes { offset : end }));
+}
+
+#[test]
+fn words_must_fit_the_type() {
+    let bytes = binary::encode(&[1, 1 << 40]);
+    match loader::load_bytes::<i32>(bytes.clone()) {
+        Err(LoadError::Binary(error)) => assert_eq!(error, BinaryError::OutOfRange { index : 1, value : 1 << 40 }),
+        other => panic!("expected OutOfRange, got {:?}", other),
+    }
+    assert_eq!(loader::load_bytes::<Num>(bytes).unwrap(), vec![1, 1 << 40]);
+}
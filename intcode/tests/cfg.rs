@@ -0,0 +1,69 @@
+use intcode::cfg::{Cfg, Edge, EdgeKind, SelfModification};
+use intcode::loader;
+
+// 0: stores the return address 7 and calls 8
+// 7: halts
+// 8: a loop counting [31] down
+// 15: writes into the instruction at 19, then returns through [30]
+const PROGRAM : &str = "1101,0,7,30, 1105,1,8, 99, \
+                        1001,31,-1,31, 1005,31,8, \
+                        1101,5,6,19, 104,0, 106,0,30, \
+                        0,0,0,0,0,0, 0,3";
+
+fn program() -> Vec<i64> {
+    loader::parse(PROGRAM).unwrap()
+}
+
+#[test]
+fn finds_blocks_and_edges() {
+    let cfg = Cfg::build(&program());
+
+    let starts : Vec<usize> = cfg.blocks.keys().copied().collect();
+    assert_eq!(starts, vec![0, 7, 8, 15]);
+
+    assert_eq!(cfg.blocks[&0].instructions, vec![0, 4]);
+    assert_eq!(cfg.blocks[&0].edges, vec![
+        Edge { to : 8, kind : EdgeKind::Taken },
+        Edge { to : 7, kind : EdgeKind::Return },
+    ]);
+    assert!(cfg.blocks[&7].edges.is_empty());
+    assert_eq!(cfg.blocks[&8].edges, vec![
+        Edge { to : 8, kind : EdgeKind::Taken },
+        Edge { to : 15, kind : EdgeKind::FallThrough },
+    ]);
+    assert_eq!(cfg.blocks[&15].instructions, vec![15, 19, 21]);
+    assert_eq!(cfg.blocks[&15].end, 24);
+    assert_eq!(cfg.edges(), 4);
+}
+
+#[test]
+fn flags_indirect_jumps_and_writes_into_code() {
+    let cfg = Cfg::build(&program());
+
+    assert!(cfg.blocks[&15].indirect);
+    assert_eq!(cfg.indirect_jumps(), vec![21]);
+    assert_eq!(cfg.self_modifying, vec![SelfModification { pc : 15, address : 19 }]);
+}
+
+#[test]
+fn invalid_words_end_blocks() {
+    // The add turns the word at 6 into a valid instruction at run time
+    let cfg = Cfg::build(&loader::parse::<i64>("3,225,1,225,6,6,1100,1,238,225,99").unwrap());
+
+    assert_eq!(cfg.blocks.len(), 1);
+    assert!(cfg.blocks[&0].invalid);
+    assert_eq!(cfg.self_modifying, vec![SelfModification { pc : 2, address : 6 }]);
+}
+
+#[test]
+fn exports_dot() {
+    let program = program();
+    let dot = Cfg::build(&program).to_dot(&program);
+
+    assert!(dot.starts_with("digraph intcode {\n"));
+    assert!(dot.contains("    b0 -> b8 [style=solid];\n"), "{}", dot);
+    assert!(dot.contains("    b0 -> b7 [style=dotted];\n"), "{}", dot);
+    assert!(dot.contains("    b8 -> b15 [style=dashed];\n"), "{}", dot);
+    assert!(dot.contains("    b15 [label=\"15: add 5, 6, [19]\\l19: out 0\\l21: jz 0, [30]\\l\", color=red, style=filled, fillcolor=orange];\n"), "{}", dot);
+    assert!(dot.ends_with("}\n"));
+}
@@ -0,0 +1,68 @@
+use intcode::coverage::{Branch, Coverage, FormatError, Mark};
+use intcode::loader;
+use intcode::machine::{Limits, Machine, Status};
+
+// Outputs 1 if the input is 8 and 0 otherwise, with a branch on the result
+const PROGRAM : &str = "3,20,1008,20,8,21,1005,21,14,104,0,1105,1,16,104,1,99,0,0,0,0,0";
+
+fn run(input : i64) -> Coverage {
+    let mut machine = Machine::new(loader::parse(PROGRAM).unwrap());
+    let mut coverage = Coverage::new();
+
+    machine.push_input(input);
+    assert_eq!(machine.run_traced(&Limits::none(), &mut coverage), Ok(Status::Halted));
+    coverage
+}
+
+#[test]
+fn records_executed_instructions_and_branches() {
+    let coverage = run(8);
+
+    assert_eq!(coverage.hits(0), 1);
+    assert_eq!(coverage.hits(9), 0);
+    assert_eq!(coverage.hits(14), 1);
+    assert!(coverage.is_data(20));
+    assert!(coverage.is_data(21));
+    assert_eq!(coverage.branch(6), Some(Branch { taken : 1, not_taken : 0 }));
+    // Jumps on an immediate condition aren't branches
+    assert_eq!(coverage.branch(11), None);
+}
+
+#[test]
+fn merged_runs_cover_both_directions() {
+    let program = loader::parse(PROGRAM).unwrap();
+    let mut coverage = run(8);
+    assert_eq!(coverage.summary(&program), ((5, 7), (0, 1)));
+
+    coverage.merge(&run(3));
+    assert_eq!(coverage.branch(6), Some(Branch { taken : 1, not_taken : 1 }));
+    assert_eq!(coverage.hits(0), 2);
+    assert_eq!(coverage.summary(&program), ((7, 7), (1, 1)));
+}
+
+#[test]
+fn listing_marks_every_word() {
+    let program = loader::parse(PROGRAM).unwrap();
+    let coverage = run(8);
+
+    let marks : Vec<(usize, Mark)> = coverage.classify(&program).into_iter().map(|(pc, _, mark)| (pc, mark)).collect();
+    assert_eq!(&marks[..8], &[
+        (0, Mark::Executed), (2, Mark::Executed), (6, Mark::Executed), (9, Mark::Unexecuted),
+        (11, Mark::Unexecuted), (14, Mark::Executed), (16, Mark::Executed), (17, Mark::Untouched),
+    ]);
+    assert_eq!(marks[10], (20, Mark::Data));
+
+    let listing = coverage.listing(&program);
+    assert!(listing.contains("+      6          1  jnz [21], 14  (taken 1, not taken 0) !"), "{}", listing);
+    assert!(listing.contains("-      9             out 0"), "{}", listing);
+    assert!(listing.contains("d     20             data 0"), "{}", listing);
+}
+
+#[test]
+fn saved_coverage_reads_back() {
+    let mut coverage = run(8);
+    coverage.merge(&run(1));
+
+    assert_eq!(Coverage::parse(&coverage.save()), Ok(coverage));
+    assert_eq!(Coverage::parse("exec 1 2\nbranch 4 x 1\n"), Err(FormatError { line : 2 }));
+}
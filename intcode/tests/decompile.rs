@@ -0,0 +1,53 @@
+use intcode::decompile::decompile;
+use intcode::loader;
+
+// main grows the relative base, calls 16 with the argument 5 and prints [60].
+// The function at 16 has a frame of 2: it adds its argument down to 1 into
+// [60], then stores 1 or 2 into [62] depending on whether that made 15.
+const PROGRAM : &str = "109,100, 21101,0,5,1, 21101,0,13,0, 1105,1,16, 4,60, 99, \
+                        109,2, 201,-1,60,60, 21201,-1,-1,-1, 1205,-1,18, \
+                        1008,60,15,61, 1006,61,43, 1101,0,1,62, 1105,1,47, 1101,0,2,62, \
+                        109,-2, 2105,1,0, \
+                        0,0,0,0,0,0,0,0,0,0,0";
+
+const EXPECTED : &str = "\
+// Variables
+//   var_60 = mem[60], 4 references
+
+fn main() {
+    rb += 100
+    fn_16(5)
+    output(var_60)
+    halt
+}
+
+fn fn_16(arg1) { // frame of 2
+    do {
+        var_60 = arg1 + var_60
+        arg1 = arg1 - 1
+    } while (arg1 != 0)
+    mem[61] = var_60 == 15
+    if (mem[61] != 0) {
+        mem[62] = 1
+    } else {
+        mem[62] = 2
+    }
+    return
+}
+";
+
+#[test]
+fn structures_loops_branches_and_calls() {
+    let program = loader::parse(PROGRAM).unwrap();
+    assert_eq!(decompile(&program), EXPECTED);
+}
+
+#[test]
+fn falls_back_to_labels() {
+    // Jumping back into the middle of the if can't be structured
+    let program = loader::parse("3,20, 1006,20,9, 104,1, 4,20, 1105,1,7, 99").unwrap();
+    let text = decompile(&program);
+
+    assert!(text.contains("goto L7"), "{}", text);
+    assert!(text.contains("L7:"), "{}", text);
+}
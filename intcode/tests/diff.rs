@@ -0,0 +1,63 @@
+use intcode::diff::{self, Change, Snapshot};
+use intcode::loader;
+use intcode::machine::{Machine, Status};
+
+// Stores an input at 20 and the input plus one at 21, then waits for
+// another input to store at 23
+const STORES : &str = "3,20, 1001,20,1,21, 3,23, 99";
+
+fn snapshot(memory : &[i64]) -> Snapshot {
+    Snapshot { memory : memory.to_vec(), ..Snapshot::default() }
+}
+
+#[test]
+fn lists_changed_addresses() {
+    let diff = diff::diff(&snapshot(&[1, 2, 3, 4]), &snapshot(&[1, 5, 3, 4, 0, 6]));
+
+    assert_eq!(diff.changes, vec![
+        Change { address : 1, old : 2, new : 5, writer : None },
+        Change { address : 5, old : 0, new : 6, writer : None },
+    ]);
+    assert!(diff::diff(&snapshot(&[1, 2]), &snapshot(&[1, 2, 0])).is_empty());
+}
+
+#[test]
+fn groups_consecutive_addresses() {
+    let diff = diff::diff(&snapshot(&[0, 0, 0, 0, 0, 0]), &snapshot(&[1, 1, 0, 1, 1, 1]));
+    let ranges : Vec<(usize, usize, usize)> = diff.ranges().iter()
+        .map(|range| (range.start, range.end, range.changes.len()))
+        .collect();
+
+    assert_eq!(ranges, vec![(0, 1, 2), (3, 5, 3)]);
+}
+
+#[test]
+fn annotates_changes_with_their_writers() {
+    let mut machine = Machine::new(loader::parse(STORES).unwrap()).history_length(100);
+    let before = Snapshot::of(&machine);
+
+    machine.push_input(7);
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    let after = Snapshot::of(&machine);
+
+    let diff = diff::diff(&before, &after);
+    assert_eq!(diff.pc, (0, 6));
+    let ranges = diff.ranges();
+    assert_eq!(ranges.len(), 1);
+    assert_eq!((ranges[0].start, ranges[0].end), (20, 21));
+    assert_eq!(ranges[0].writers(), vec![0, 2]);
+    assert_eq!(ranges[0].changes[1], Change { address : 21, old : 0, new : 8, writer : Some(2) });
+}
+
+#[test]
+fn snapshots_round_trip() {
+    let mut machine = Machine::new(loader::parse(STORES).unwrap()).history_length(100);
+    machine.push_input(7);
+    machine.run().unwrap();
+
+    let snapshot = Snapshot::of(&machine);
+    assert_eq!(Snapshot::parse(&snapshot.save()), Ok(snapshot));
+
+    assert_eq!(Snapshot::parse("pc 1\nmemory 1,x").unwrap_err().line, 2);
+    assert_eq!(Snapshot::parse("pc 1\nwriter 3").unwrap_err().line, 2);
+}
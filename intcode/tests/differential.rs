@@ -0,0 +1,118 @@
+// Random programs run on several interpreters, which should agree on their
+// outputs, final memory and how they stop.
+
+use intcode::Num;
+use intcode::fuzz::{self, CachedVm, Case, Config, End, Feed, Implementation, ModeRule, Reference, Rng, ThreadedVm, Vm};
+
+const CASES : u64 = 500;
+
+#[test]
+fn generation_is_reproducible() {
+    let config = Config::default();
+    assert_eq!(fuzz::generate(&mut Rng::new(7), &config), fuzz::generate(&mut Rng::new(7), &config));
+    assert_ne!(fuzz::generate(&mut Rng::new(7), &config), fuzz::generate(&mut Rng::new(8), &config));
+}
+
+#[test]
+fn generated_programs_are_well_formed() {
+    let config = Config::default();
+    for seed in 0..CASES {
+        let case = fuzz::generate(&mut Rng::new(seed), &config);
+        let end = Vm(Feed::Upfront).execute(&case, config.fuel).end;
+        assert!([End::Halted, End::NeedsInput, End::OutOfFuel].contains(&end), "seed {} ended with {:?}", seed, end);
+    }
+}
+
+#[test]
+fn writes_get_a_data_word_when_there_is_no_data() {
+    let config = Config { data : 0, ..Config::default() };
+    for seed in 0..CASES {
+        let case = fuzz::generate(&mut Rng::new(seed), &config);
+        let end = Vm(Feed::Upfront).execute(&case, config.fuel).end;
+        assert!([End::Halted, End::NeedsInput, End::OutOfFuel].contains(&end), "seed {} ended with {:?}", seed, end);
+    }
+
+    // Writing into the code is allowed, so no data is needed
+    let config = Config { data : 0, self_modifying : true, ..Config::default() };
+    let one_word = Config { data : 1, ..config.clone() };
+    let length = |config : &Config| fuzz::generate(&mut Rng::new(0), config).program.len();
+    assert_eq!(length(&config) + 1, length(&one_word));
+}
+
+#[test]
+fn input_feeding_does_not_matter() {
+    let config = Config { self_modifying : true, ..Config::default() };
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &Vm(Feed::OnDemand)];
+
+    if let Some(counterexample) = fuzz::differential(&implementations, &config, 0, CASES) {
+        panic!("{:?}", counterexample);
+    }
+}
+
+#[test]
+fn decode_cache_does_not_matter() {
+    let config = Config { self_modifying : true, ..Config::default() };
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &CachedVm];
+
+    if let Some(counterexample) = fuzz::differential(&implementations, &config, 0, CASES) {
+        panic!("{:?}", counterexample);
+    }
+}
+
+#[test]
+fn threaded_engine_agrees_with_machine() {
+    let config = Config { self_modifying : true, ..Config::default() };
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &ThreadedVm];
+
+    if let Some(counterexample) = fuzz::differential(&implementations, &config, 0, CASES) {
+        panic!("{:?}", counterexample);
+    }
+}
+
+#[test]
+fn machine_agrees_with_day9_model() {
+    let config = Config::default();
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &Reference(ModeRule::Day9)];
+
+    if let Some(counterexample) = fuzz::differential(&implementations, &config, 0, CASES) {
+        panic!("{:?}", counterexample);
+    }
+}
+
+#[test]
+fn machine_agrees_with_day5_model_without_relative_mode() {
+    let config = Config { relative : false, ..Config::default() };
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &Reference(ModeRule::Day5)];
+
+    if let Some(counterexample) = fuzz::differential(&implementations, &config, 0, CASES) {
+        panic!("{:?}", counterexample);
+    }
+}
+
+#[test]
+fn day5_mode_rule_diverges_and_shrinks() {
+    let config = Config::default();
+    let implementations : [&dyn Implementation; 2] = [&Vm(Feed::Upfront), &Reference(ModeRule::Day5)];
+
+    let counterexample = fuzz::differential(&implementations, &config, 0, CASES).expect("no divergence found");
+
+    assert!(counterexample.shrunk.program.len() <= 4, "{:?}", counterexample.shrunk);
+    assert!(counterexample.shrunk.program.len() < counterexample.original.program.len());
+    assert!(fuzz::compare(&implementations, &counterexample.shrunk, config.fuel).is_some());
+}
+
+#[test]
+fn shrinking_keeps_the_failure() {
+    let case = Case { program : vec![1, 57, 3, -40, 99, 12, 8], inputs : vec![5, 6] };
+    let shrunk = fuzz::shrink(case, |case| case.program.iter().any(|&word| word > 10));
+
+    assert_eq!(shrunk, Case { program : vec![11], inputs : vec![] });
+}
+
+#[test]
+fn shrinking_handles_the_smallest_value() {
+    let case = Case { program : vec![Num::MIN, 99], inputs : vec![] };
+    let shrunk = fuzz::shrink(case, |case| case.program.first().is_some_and(|&word| word < -1000));
+
+    assert_eq!(shrunk, Case { program : vec![-1001], inputs : vec![] });
+}
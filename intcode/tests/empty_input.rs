@@ -0,0 +1,89 @@
+use std::sync::mpsc::channel;
+use std::thread;
+
+use intcode::loader;
+use intcode::machine::{EmptyInput, Error, Machine, Status};
+
+// Prints two inputs
+const ECHO : &str = "3,9, 4,9, 3,9, 4,9, 99, 0";
+
+fn machine(policy : EmptyInput) -> Machine {
+    Machine::new(loader::parse(ECHO).unwrap()).empty_input(policy)
+}
+
+#[test]
+fn yields_by_default() {
+    let mut machine = Machine::new(loader::parse(ECHO).unwrap());
+    machine.push_input(1);
+
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    assert_eq!(machine.pc(), 4);
+    machine.push_input(2);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[1, 2]);
+}
+
+#[test]
+fn fails_without_input() {
+    let mut machine = machine(EmptyInput::Error);
+    machine.push_input(1);
+
+    assert_eq!(machine.run(), Err(Error::NoInput { pc : 4 }));
+    assert_eq!(machine.outputs(), &[1]);
+}
+
+#[test]
+fn reads_a_default_value() {
+    let mut machine = machine(EmptyInput::Default(-1));
+    machine.push_input(1);
+
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[1, -1]);
+}
+
+#[test]
+fn blocks_on_the_connected_channel() {
+    let (input, inputs) = channel();
+    let (outputs, output) = channel();
+    let mut machine = machine(EmptyInput::Block).connect(inputs, outputs);
+
+    let running = thread::spawn(move || {
+        let status = machine.run();
+        (status, machine.take_outputs())
+    });
+    input.send(1).unwrap();
+    assert_eq!(output.recv(), Ok(1));
+    input.send(2).unwrap();
+    assert_eq!(output.recv(), Ok(2));
+
+    assert_eq!(running.join().unwrap(), (Ok(Status::Halted), vec![1, 2]));
+}
+
+#[test]
+fn blocking_fails_once_nobody_can_send() {
+    let mut unconnected = machine(EmptyInput::Block);
+    assert_eq!(unconnected.run(), Err(Error::NoInput { pc : 0 }));
+
+    let (input, inputs) = channel();
+    let (outputs, _) = channel();
+    let mut machine = machine(EmptyInput::Block).connect(inputs, outputs);
+    input.send(1).unwrap();
+    drop(input);
+
+    assert_eq!(machine.run(), Err(Error::NoInput { pc : 4 }));
+    // The output was kept even though nobody received it
+    assert_eq!(machine.outputs(), &[1]);
+}
+
+#[test]
+fn connected_machines_yield_on_an_empty_channel() {
+    let (input, inputs) = channel();
+    let (outputs, _output) = channel();
+    let mut machine = machine(EmptyInput::Yield).connect(inputs, outputs);
+
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    input.send(5).unwrap();
+    input.send(6).unwrap();
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[5, 6]);
+}
@@ -0,0 +1,269 @@
+// Example programs from the puzzle texts of days 2, 5, 7 and 9, checked
+// against the outputs the puzzles give for them.
+
+use intcode::Num;
+use intcode::loader;
+use intcode::machine::{Error, Machine, Status};
+
+fn machine(program : &str) -> Machine {
+    Machine::new(loader::parse(program).unwrap())
+}
+
+fn run(program : &str, inputs : &[Num]) -> Vec<Num> {
+    let mut machine = machine(program);
+    for &input in inputs {
+        machine.push_input(input);
+    }
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    machine.take_outputs()
+}
+
+fn final_memory(program : &str) -> Vec<Num> {
+    let mut machine = machine(program);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    machine.memory().to_vec()
+}
+
+fn permutations(values : &[Num]) -> Vec<Vec<Num>> {
+    if values.is_empty() {
+        return vec![vec![]];
+    }
+    let mut result = Vec::new();
+    for i in 0..values.len() {
+        let mut rest = values.to_vec();
+        let first = rest.remove(i);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, first);
+            result.push(permutation);
+        }
+    }
+    result
+}
+
+fn amplifiers(program : &str, phases : &[Num]) -> Num {
+    phases.iter().fold(0, |signal, &phase| run(program, &[phase, signal])[0])
+}
+
+fn feedback_loop(program : &str, phases : &[Num]) -> Num {
+    let mut amplifiers : Vec<Machine> = phases.iter().map(|&phase| {
+        let mut amplifier = machine(program);
+        amplifier.push_input(phase);
+        amplifier
+    }).collect();
+
+    let mut signal = 0;
+    loop {
+        for amplifier in amplifiers.iter_mut() {
+            amplifier.push_input(signal);
+            amplifier.run().unwrap();
+            signal = *amplifier.take_outputs().last().unwrap();
+        }
+        if amplifiers.iter().all(Machine::is_halted) {
+            return signal;
+        }
+    }
+}
+
+fn best_signal(program : &str, phases : &[Num], run : fn(&str, &[Num]) -> Num) -> (Num, Vec<Num>) {
+    permutations(phases).into_iter()
+        .map(|permutation| (run(program, &permutation), permutation))
+        .max()
+        .unwrap()
+}
+
+// Day 2
+
+#[test]
+fn day2_add_and_multiply() {
+    assert_eq!(final_memory("1,9,10,3,2,3,11,0,99,30,40,50"), vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]);
+    assert_eq!(final_memory("1,0,0,0,99"), vec![2, 0, 0, 0, 99]);
+    assert_eq!(final_memory("2,3,0,3,99"), vec![2, 3, 0, 6, 99]);
+    assert_eq!(final_memory("2,4,4,5,99,0"), vec![2, 4, 4, 5, 99, 9801]);
+    assert_eq!(final_memory("1,1,1,4,99,5,6,0,99"), vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+}
+
+// Day 5
+
+#[test]
+fn day5_input_output() {
+    assert_eq!(run("3,0,4,0,99", &[1234]), vec![1234]);
+}
+
+#[test]
+fn day5_parameter_modes() {
+    assert_eq!(final_memory("1002,4,3,4,33"), vec![1002, 4, 3, 4, 99]);
+    assert_eq!(final_memory("1101,100,-1,4,0"), vec![1101, 100, -1, 4, 99]);
+}
+
+#[test]
+fn day5_comparisons() {
+    let equal_position = "3,9,8,9,10,9,4,9,99,-1,8";
+    let less_position = "3,9,7,9,10,9,4,9,99,-1,8";
+    let equal_immediate = "3,3,1108,-1,8,3,4,3,99";
+    let less_immediate = "3,3,1107,-1,8,3,4,3,99";
+
+    for &(input, equal, less) in &[(7, 0, 1), (8, 1, 0), (9, 0, 0)] {
+        assert_eq!(run(equal_position, &[input]), vec![equal]);
+        assert_eq!(run(less_position, &[input]), vec![less]);
+        assert_eq!(run(equal_immediate, &[input]), vec![equal]);
+        assert_eq!(run(less_immediate, &[input]), vec![less]);
+    }
+}
+
+#[test]
+fn day5_jumps() {
+    let position = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+    let immediate = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1";
+
+    for &(input, expected) in &[(0, 0), (5, 1), (-3, 1)] {
+        assert_eq!(run(position, &[input]), vec![expected]);
+        assert_eq!(run(immediate, &[input]), vec![expected]);
+    }
+}
+
+#[test]
+fn day5_compare_to_eight() {
+    let program = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+                   1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+                   999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+
+    assert_eq!(run(program, &[7]), vec![999]);
+    assert_eq!(run(program, &[8]), vec![1000]);
+    assert_eq!(run(program, &[9]), vec![1001]);
+}
+
+// Day 7
+
+const AMPLIFIERS_1 : &str = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+const AMPLIFIERS_2 : &str = "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0";
+const AMPLIFIERS_3 : &str = "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,\
+                             1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0";
+const FEEDBACK_1 : &str = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+                           27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+const FEEDBACK_2 : &str = "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,\
+                           -5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,\
+                           55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10";
+
+#[test]
+fn day7_amplifiers() {
+    assert_eq!(amplifiers(AMPLIFIERS_1, &[4, 3, 2, 1, 0]), 43210);
+    assert_eq!(amplifiers(AMPLIFIERS_2, &[0, 1, 2, 3, 4]), 54321);
+    assert_eq!(amplifiers(AMPLIFIERS_3, &[1, 0, 4, 3, 2]), 65210);
+}
+
+#[test]
+fn day7_best_phases() {
+    let phases = [0, 1, 2, 3, 4];
+
+    assert_eq!(best_signal(AMPLIFIERS_1, &phases, amplifiers), (43210, vec![4, 3, 2, 1, 0]));
+    assert_eq!(best_signal(AMPLIFIERS_2, &phases, amplifiers), (54321, vec![0, 1, 2, 3, 4]));
+    assert_eq!(best_signal(AMPLIFIERS_3, &phases, amplifiers), (65210, vec![1, 0, 4, 3, 2]));
+}
+
+#[test]
+fn day7_feedback_loop() {
+    let phases = [5, 6, 7, 8, 9];
+
+    assert_eq!(feedback_loop(FEEDBACK_1, &[9, 8, 7, 6, 5]), 139629729);
+    assert_eq!(feedback_loop(FEEDBACK_2, &[9, 7, 8, 5, 6]), 18216);
+    assert_eq!(best_signal(FEEDBACK_1, &phases, feedback_loop), (139629729, vec![9, 8, 7, 6, 5]));
+    assert_eq!(best_signal(FEEDBACK_2, &phases, feedback_loop), (18216, vec![9, 7, 8, 5, 6]));
+}
+
+// Day 9
+
+const QUINE : &str = include_str!("../../day9/quine.txt");
+
+#[test]
+fn day9_quine() {
+    let program : Vec<Num> = loader::parse(QUINE).unwrap();
+    assert_eq!(run(QUINE, &[]), program);
+}
+
+#[test]
+fn day9_sixteen_digits() {
+    let outputs = run(include_str!("../../day9/16digit.txt"), &[]);
+    assert_eq!(outputs, vec![1219070632396864]);
+    assert_eq!(outputs[0].to_string().len(), 16);
+}
+
+#[test]
+fn day9_large_number() {
+    assert_eq!(run(include_str!("../../day9/largenum.txt"), &[]), vec![1125899906842624]);
+}
+
+// Addressing modes
+
+#[test]
+fn read_modes() {
+    assert_eq!(run("4,3,99,42", &[]), vec![42]);
+    assert_eq!(run("104,42,99", &[]), vec![42]);
+    assert_eq!(run("109,3,204,3,99,0,42", &[]), vec![42]);
+
+    // Operands in mixed modes: immediate + relative, relative + position
+    assert_eq!(run("109,10,2101,5,1,12,4,12,99,0,0,6", &[]), vec![11]);
+    assert_eq!(run("109,10,201,1,9,12,4,12,99,7,0,6", &[]), vec![13]);
+}
+
+#[test]
+fn write_modes() {
+    assert_eq!(final_memory("1,0,0,5,99,0"), vec![1, 0, 0, 5, 99, 2]);
+    assert_eq!(final_memory("109,10,21101,3,4,-2,99")[8], 7);
+    assert_eq!(final_memory("109,10,21102,3,4,0,99")[10], 12);
+    assert_eq!(final_memory("109,20,21107,1,2,0,21108,2,2,1,99")[20..22], [1, 1]);
+    assert_eq!(final_memory("109,20,21107,2,1,0,21108,2,3,1,99")[20..22], [0, 0]);
+
+    let mut input = machine("3,7,109,10,203,0,99,0");
+    input.push_input(5);
+    input.push_input(6);
+    assert_eq!(input.run(), Ok(Status::Halted));
+    assert_eq!(input.memory()[7], 5);
+    assert_eq!(input.memory()[10], 6);
+}
+
+#[test]
+fn immediate_writes_are_rejected() {
+    assert_eq!(machine("11101,1,1,0,99").run(), Err(Error::InvalidMode { pc : 0, mode : 1 }));
+
+    let mut input = machine("103,0,99");
+    input.push_input(1);
+    assert_eq!(input.run(), Err(Error::InvalidMode { pc : 0, mode : 1 }));
+}
+
+#[test]
+fn relative_base_modes() {
+    assert_eq!(run("9,6,204,1,99,55,4", &[]), vec![55]);
+    assert_eq!(run("109,5,209,2,204,0,99,3,66", &[]), vec![66]);
+    assert_eq!(run("109,10,109,-7,204,4,99,42", &[]), vec![42]);
+}
+
+#[test]
+fn relative_jumps() {
+    assert_eq!(run("109,12,2205,0,1,104,0,99,104,1,99,0,1,8", &[]), vec![1]);
+    assert_eq!(run("109,12,2206,0,1,104,0,99,104,1,99,0,0,8", &[]), vec![1]);
+    assert_eq!(run("109,12,2205,0,1,104,0,99,104,1,99,0,0,8", &[]), vec![0]);
+}
+
+#[test]
+fn memory_beyond_the_program_is_zero() {
+    assert_eq!(run("4,1000,99", &[]), vec![0]);
+    assert_eq!(final_memory("1101,2,3,1000,99").len(), 1001);
+}
+
+#[test]
+fn waits_for_input() {
+    let mut machine = machine("3,0,4,0,99");
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    assert_eq!(machine.pc(), 0);
+    machine.push_input(7);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.take_outputs(), vec![7]);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(machine("1,0,0,0,42").run(), Err(Error::UnknownOpcode { pc : 4, opcode : 42 }));
+    assert_eq!(machine("4,-1,99").run(), Err(Error::NegativeAddress { pc : 0, address : -1 }));
+    assert_eq!(machine("304,0,99").run(), Err(Error::InvalidMode { pc : 0, mode : 3 }));
+}
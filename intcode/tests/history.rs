@@ -0,0 +1,158 @@
+use intcode::Num;
+use intcode::loader;
+use intcode::machine::{CodeWrite, Machine, Status};
+
+// Reads two numbers into 17 and 18, stores their sum at 19 and prints it,
+// then adds one to it and prints it again
+const SUM : &str = "3,17, 3,18, 1,17,18,19, 4,19, 1001,19,1,19, 4,19, 99, 0,0,0";
+
+fn machine(program : &str, history : usize, inputs : &[Num]) -> Machine {
+    let mut machine = Machine::new(loader::parse(program).unwrap()).history_length(history);
+    for &input in inputs {
+        machine.push_input(input);
+    }
+    machine
+}
+
+#[test]
+fn steps_back_to_the_start() {
+    let mut machine = machine(SUM, 100, &[2, 3]);
+    let initial = machine.memory().to_vec();
+
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[5, 6]);
+    assert_eq!(machine.history().unwrap().len(), 7);
+
+    while machine.step_back() {}
+
+    assert_eq!(machine.memory(), &initial[..]);
+    assert_eq!(machine.pc(), 0);
+    assert_eq!(machine.executed(), 0);
+    assert!(machine.outputs().is_empty());
+    assert!(!machine.is_halted());
+
+    // The inputs are back in the queue, so the run can be replayed
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[5, 6]);
+}
+
+#[test]
+fn finds_the_last_writer() {
+    let mut machine = machine(SUM, 100, &[2, 3]);
+    machine.run().unwrap();
+
+    let write = machine.last_writer(19).unwrap();
+    assert_eq!((write.pc, write.old, write.new), (10, 5, 6));
+    assert_eq!(machine.last_writer(17).map(|write| write.pc), Some(0));
+    assert_eq!(machine.last_writer(0), None);
+}
+
+#[test]
+fn reverse_continues_to_earlier_writes() {
+    let mut machine = machine(SUM, 100, &[2, 3]);
+    machine.run().unwrap();
+
+    let write = machine.reverse_continue(19).unwrap();
+    assert_eq!(write.pc, 10);
+    assert_eq!(machine.pc(), 10);
+    assert_eq!(machine.memory()[19], 5);
+    assert_eq!(machine.outputs(), &[5]);
+
+    let write = machine.reverse_continue(19).unwrap();
+    assert_eq!((write.pc, write.old, write.new), (4, 0, 5));
+    assert_eq!(machine.pc(), 4);
+
+    assert_eq!(machine.reverse_continue(19), None);
+    assert_eq!(machine.pc(), 0);
+}
+
+#[test]
+fn history_is_bounded() {
+    let mut machine = machine(SUM, 3, &[2, 3]);
+    machine.run().unwrap();
+
+    let history = machine.history().unwrap();
+    assert_eq!((history.len(), history.dropped()), (3, 4));
+    // The input at 0 fell off the end
+    assert_eq!(machine.last_writer(17), None);
+
+    while machine.step_back() {}
+    assert_eq!(machine.pc(), 10);
+}
+
+#[test]
+fn waiting_for_input_is_not_recorded() {
+    let mut machine = machine(SUM, 100, &[2]);
+
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    assert_eq!(machine.history().unwrap().len(), 1);
+
+    assert!(machine.step_back());
+    assert_eq!(machine.pc(), 0);
+    assert!(!machine.step_back());
+}
+
+#[test]
+fn undone_code_writes_are_decoded_again() {
+    // Prints 1, turns the print into a halt and jumps back to it
+    let program = "104,1, 1101,0,99,0, 1105,1,0";
+    let mut machine = machine(program, 10, &[]).decode_cache(true);
+
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[1]);
+
+    // The cached halt must not outlive the write that made it
+    while machine.step_back() {}
+    assert_eq!(machine.memory()[0], 104);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.outputs(), &[1]);
+}
+
+#[test]
+fn stepping_back_forgets_code_and_code_writes() {
+    let program = "104,1, 1101,0,99,0, 1105,1,0";
+    let mut machine = machine(program, 10, &[]);
+
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.code_writes(), [CodeWrite { pc : 2, address : 0, count : 1 }]);
+
+    // The halt at 0 was code already, undoing it changes nothing
+    assert!(machine.step_back());
+    assert_eq!(machine.code_writes(), [CodeWrite { pc : 2, address : 0, count : 1 }]);
+    assert!(machine.is_code(0) && machine.is_code(8));
+
+    // Before the jump ran it isn't code, and before the write there is no
+    // code write
+    assert!(machine.step_back());
+    assert!(!machine.is_code(6) && !machine.is_code(8));
+    assert!(machine.step_back());
+    assert_eq!(machine.code_writes(), []);
+    assert!(machine.is_code(0) && machine.is_code(1) && !machine.is_code(2));
+    assert!(machine.step_back());
+    assert!(!machine.is_code(0));
+}
+
+#[test]
+fn code_read_while_waiting_for_input_is_forgotten_too() {
+    let mut machine = machine(SUM, 100, &[]);
+
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+    assert!(machine.is_code(0));
+    machine.push_input(1);
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+
+    while machine.step_back() {}
+    assert!(!machine.is_code(0) && !machine.is_code(2));
+}
+
+#[test]
+fn off_by_default() {
+    let mut machine = Machine::new(loader::parse(SUM).unwrap());
+    machine.push_input(1);
+    machine.push_input(1);
+    machine.run().unwrap();
+
+    assert!(machine.history().is_none());
+    assert!(!machine.step_back());
+    assert_eq!(machine.last_writer(19), None);
+}
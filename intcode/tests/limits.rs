@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use intcode::loader;
+use intcode::machine::{Limits, Machine, Status};
+
+// Jumps back to itself forever
+const LOOP : &str = "1105,1,0";
+
+fn machine(program : &str) -> Machine {
+    Machine::new(loader::parse(program).unwrap())
+}
+
+#[test]
+fn instruction_budget_stops_and_resumes() {
+    let mut machine = machine(LOOP);
+    let limits = Limits::none().instructions(100);
+
+    assert_eq!(machine.run_with(&limits), Ok(Status::BudgetExceeded));
+    assert_eq!(machine.executed(), 100);
+    assert_eq!(machine.pc(), 0);
+
+    assert_eq!(machine.run_with(&limits), Ok(Status::BudgetExceeded));
+    assert_eq!(machine.executed(), 200);
+}
+
+#[test]
+fn budget_counts_the_halt() {
+    // An add and a halt
+    let program = "1101,1,1,5,99,0";
+
+    let mut short = machine(program);
+    assert_eq!(short.run_with(&Limits::none().instructions(1)), Ok(Status::BudgetExceeded));
+    assert_eq!(short.pc(), 4);
+    assert_eq!(short.memory()[5], 2);
+    assert_eq!(short.run_with(&Limits::none().instructions(1)), Ok(Status::Halted));
+
+    let mut exact = machine(program);
+    assert_eq!(exact.run_with(&Limits::none().instructions(2)), Ok(Status::Halted));
+    assert_eq!(exact.executed(), 2);
+    assert_eq!(exact.run_with(&Limits::none().instructions(0)), Ok(Status::Halted));
+}
+
+#[test]
+fn input_is_reported_before_the_budget() {
+    let mut machine = machine("3,0,4,0,99");
+    let limits = Limits::none().instructions(10);
+
+    assert_eq!(machine.run_with(&limits), Ok(Status::NeedsInput));
+    machine.push_input(3);
+    assert_eq!(machine.run_with(&limits), Ok(Status::Halted));
+    assert_eq!(machine.take_outputs(), vec![3]);
+}
+
+#[test]
+fn deadline_stops_an_infinite_loop() {
+    let mut machine = machine(LOOP);
+    let start = Instant::now();
+
+    assert_eq!(machine.run_with(&Limits::none().timeout(Duration::from_millis(20))), Ok(Status::BudgetExceeded));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert!(machine.executed() > 0);
+}
+
+#[test]
+fn first_limit_reached_wins() {
+    let mut machine = machine(LOOP);
+    let limits = Limits::none().timeout(Duration::from_secs(60)).instructions(500);
+
+    assert_eq!(machine.run_with(&limits), Ok(Status::BudgetExceeded));
+    assert_eq!(machine.executed(), 500);
+}
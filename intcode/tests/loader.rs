@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use intcode::Num;
+use intcode::loader::{self, LoadError, ParseError, Source};
+
+fn parse(text : &str) -> Result<Vec<Num>, ParseError> {
+    loader::parse(text)
+}
+
+#[test]
+fn skips_whitespace_and_newlines() {
+    assert_eq!(parse("1,2,3"), Ok(vec![1, 2, 3]));
+    assert_eq!(parse("  1 ,\t-2,\n3\r\n,\n\n 4\n"), Ok(vec![1, -2, 3, 4]));
+}
+
+#[test]
+fn skips_comments() {
+    let text = "# a header\n1,2, # the add\n3 # last one\n# trailing";
+    assert_eq!(parse(text), Ok(vec![1, 2, 3]));
+}
+
+#[test]
+fn allows_a_trailing_comma() {
+    assert_eq!(parse("1,2,3,\n"), Ok(vec![1, 2, 3]));
+    assert_eq!(parse("1,2,3,,").unwrap_err().message, "expected a value before");
+}
+
+#[test]
+fn rejects_empty_programs() {
+    for text in &["", "\n\n", "# only a comment\n"] {
+        assert_eq!(parse(text).unwrap_err().message, "empty program");
+    }
+}
+
+#[test]
+fn reports_where_a_bad_token_is() {
+    assert_eq!(parse("1,2,\n  3, x4, 5"), Err(ParseError {
+        line : 2,
+        column : 6,
+        token : "x4".to_string(),
+        message : "invalid number",
+    }));
+
+    let error = parse("1,2,\n3 4").unwrap_err();
+    assert_eq!((error.line, error.column, error.token.as_str()), (2, 3, "4"));
+    assert_eq!(error.message, "expected ',' before");
+    assert_eq!(error.to_string(), "line 2, column 3: expected ',' before \"4\"");
+}
+
+#[test]
+fn values_must_fit_the_type() {
+    let error = loader::parse::<i32>("1,\n99999999999").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 1));
+}
+
+#[test]
+fn sources_from_arguments() {
+    assert!(matches!(Source::from_arg("-"), Source::Stdin));
+    assert!(matches!(Source::from_arg("input.txt"), Source::File(path) if path == Path::new("input.txt")));
+
+    let program : Vec<Num> = loader::load(&Source::Text("1,2, # text\n3".to_string())).unwrap();
+    assert_eq!(program, vec![1, 2, 3]);
+    assert!(matches!(loader::load::<Num>(&Source::Text("1,,".to_string())), Err(LoadError::Parse(_))));
+}
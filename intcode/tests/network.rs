@@ -0,0 +1,58 @@
+use intcode::loader;
+use intcode::network::{Event, Network, Packet, NAT_ADDRESS};
+use intcode::threaded::Threaded;
+
+// Sends (1 - address, address, 42), then forwards every packet it gets to
+// the NAT
+const PING : &str = "3,100, 1002,100,-1,102, 1001,102,1,102, 4,102, 4,100, 104,42, \
+                     3,101, 1008,101,-1,103, 1005,103,16, 3,104, 104,255, 4,101, 4,104, 1105,1,16";
+
+fn packet(from : i64, to : i64, x : i64, y : i64) -> Packet {
+    Packet { from, to, x, y }
+}
+
+#[test]
+fn routes_packets_between_machines() {
+    let mut network = Network::boot(&loader::parse(PING).unwrap(), 2);
+
+    assert_eq!(network.step(), Ok(vec![
+        Event::Sent(packet(0, 1, 0, 42)),
+        Event::Sent(packet(1, 0, 1, 42)),
+        Event::ToNat(packet(1, NAT_ADDRESS, 0, 42)),
+    ]));
+    assert_eq!(network.step(), Ok(vec![Event::ToNat(packet(0, NAT_ADDRESS, 1, 42))]));
+    assert_eq!(network.nat(), Some(packet(0, NAT_ADDRESS, 1, 42)));
+}
+
+#[test]
+fn nat_wakes_an_idle_network() {
+    let mut network = Network::boot(&loader::parse(PING).unwrap(), 2);
+    network.step().unwrap();
+    network.step().unwrap();
+
+    // One quiet round isn't enough
+    assert_eq!(network.step(), Ok(vec![]));
+    assert_eq!(network.step(), Ok(vec![Event::Wake(packet(NAT_ADDRESS, 0, 1, 42))]));
+    assert_eq!(network.step(), Ok(vec![Event::ToNat(packet(0, NAT_ADDRESS, 1, 42))]));
+}
+
+#[test]
+fn drops_packets_to_unknown_addresses() {
+    // Machine 1 sends to 0 and machine 0 to 1, which is missing on a network
+    // of one machine
+    let mut network = Network::new(vec![Threaded::new(loader::parse(PING).unwrap())]);
+
+    assert_eq!(network.step(), Ok(vec![Event::Dropped(packet(0, 1, 0, 42))]));
+    assert_eq!(network.machines().len(), 1);
+}
+
+#[test]
+fn waits_for_whole_packets() {
+    // Reads an input between the words of its packet, so the first round
+    // ends with two of them written
+    let program = loader::parse("3,100, 104,255, 3,101, 104,5, 3,101, 104,6, 3,101, 1105,1,14").unwrap();
+    let mut network = Network::boot(&program, 1);
+
+    assert_eq!(network.step(), Ok(vec![]));
+    assert_eq!(network.step(), Ok(vec![Event::ToNat(packet(0, NAT_ADDRESS, 5, 6))]));
+}
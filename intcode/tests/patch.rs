@@ -0,0 +1,82 @@
+use intcode::loader;
+use intcode::patch::{self, Patch, PatchError, Search, Watch, ADDRESS_LIMIT};
+
+// Multiplies the immediates at 1 and 2 into 0, like a tiny gravity assist
+// program
+const PRODUCT : &str = "1102,0,0,0, 99";
+
+#[test]
+fn parses_patches() {
+    assert_eq!(patch::parse("1=12, 2=2"), Ok(vec![Patch { address : 1, value : 12 }, Patch { address : 2, value : 2 }]));
+    assert_eq!(patch::parse("0=-1,"), Ok(vec![Patch { address : 0, value : -1 }]));
+    assert_eq!(patch::parse("1=x"), Err(PatchError::Malformed("1=x".to_string())));
+    assert_eq!(patch::parse("-1=3"), Err(PatchError::Malformed("-1=3".to_string())));
+
+    assert_eq!(patch::parse_ranges("1=0..=99,2=0..100"), Ok(vec![(1, 0..=99), (2, 0..=99)]));
+    assert!(patch::parse_ranges("1=5").is_err());
+}
+
+#[test]
+fn applies_patches() {
+    let mut memory : Vec<i32> = vec![1, 0, 0];
+    patch::apply(&mut memory, &patch::parse("1=12,4=2").unwrap()).unwrap();
+    assert_eq!(memory, vec![1, 12, 0, 0, 2]);
+
+    let too_big = Patch { address : 0, value : 1 << 40 };
+    assert_eq!(patch::apply(&mut memory, &[too_big]), Err(PatchError::OutOfRange(too_big)));
+}
+
+#[test]
+fn rejects_far_addresses() {
+    assert_eq!(patch::parse("18446744073709551615=1"), Err(PatchError::AddressTooLarge(usize::MAX)));
+    assert_eq!(patch::parse("100000000000000=1"), Err(PatchError::AddressTooLarge(100000000000000)));
+    assert_eq!(patch::parse_ranges("100000000000000=0..=1"), Err(PatchError::AddressTooLarge(100000000000000)));
+
+    let mut memory : Vec<i64> = vec![1, 2];
+    for address in [usize::MAX, ADDRESS_LIMIT] {
+        let far = Patch { address, value : 1 };
+        assert_eq!(patch::apply(&mut memory, &[far]), Err(PatchError::AddressTooLarge(address)));
+    }
+    assert_eq!(memory, vec![1, 2]);
+
+    patch::apply(&mut memory, &[Patch { address : ADDRESS_LIMIT - 1, value : 1 }]).unwrap();
+    assert_eq!(memory.len(), ADDRESS_LIMIT);
+}
+
+#[test]
+fn invalid_patches_leave_memory_untouched() {
+    let mut memory : Vec<i32> = vec![1, 2];
+
+    let patches = [Patch { address : 0, value : 7 }, Patch { address : 5, value : 1 << 40 }];
+    assert_eq!(patch::apply(&mut memory, &patches), Err(PatchError::OutOfRange(patches[1])));
+    assert_eq!(memory, vec![1, 2]);
+
+    let patches = [Patch { address : 3, value : 7 }, Patch { address : ADDRESS_LIMIT, value : 1 }];
+    assert_eq!(patch::apply(&mut memory, &patches), Err(PatchError::AddressTooLarge(ADDRESS_LIMIT)));
+    assert_eq!(memory, vec![1, 2]);
+}
+
+#[test]
+fn searches_for_address_zero() {
+    let program = loader::parse(PRODUCT).unwrap();
+    let search = Search::new(vec![(1, 0..=9), (2, 0..=9)], 42);
+
+    let (found, runs) = search.run(&program);
+    // The last range counts fastest, so 6*7 comes before 7*6
+    assert_eq!(found, Some(vec![Patch { address : 1, value : 6 }, Patch { address : 2, value : 7 }]));
+    assert_eq!(runs, 68);
+
+    let search = Search::new(vec![(1, 0..=9), (2, 0..=9)], 97);
+    assert_eq!(search.run(&program), (None, 100));
+}
+
+#[test]
+fn searches_outputs() {
+    // Prints the input plus the immediate at 4
+    let program = loader::parse("3,9, 1001,9,0,9, 4,9, 99, 0").unwrap();
+    let mut search = Search::new(vec![(4, -5..=5)], 10);
+    search.watch = Watch::Output;
+    search.inputs = vec![7];
+
+    assert_eq!(search.run(&program).0, Some(vec![Patch { address : 4, value : 3 }]));
+}
@@ -0,0 +1,99 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use intcode::disasm;
+use intcode::loader;
+use intcode::machine::{EmptyInput, Limits, Machine, Status};
+use intcode::profile::Profiler;
+
+fn profile(program : &str, inputs : &[i64]) -> (Profiler, Vec<i64>) {
+    let program : Vec<i64> = loader::parse(program).unwrap();
+    let mut machine = Machine::new(program.clone());
+    let mut profiler = Profiler::new();
+
+    for &input in inputs {
+        machine.push_input(input);
+    }
+    assert_eq!(machine.run_traced(&Limits::none(), &mut profiler), Ok(Status::Halted));
+    (profiler, program)
+}
+
+#[test]
+fn counts_per_pc_and_opcode() {
+    // Counts down from the input to zero
+    let (profiler, _) = profile("3,10,1001,10,-1,10,1005,10,2,99,0", &[4]);
+
+    assert_eq!(profiler.total(), 10);
+    assert_eq!(profiler.count_at(0), 1);
+    assert_eq!(profiler.count_at(2), 4);
+    assert_eq!(profiler.count_at(6), 4);
+    assert_eq!(profiler.opcode_counts().get(&1), Some(&4));
+    assert_eq!(profiler.opcode_counts().get(&99), Some(&1));
+}
+
+#[test]
+fn hottest_range_comes_first() {
+    let (profiler, program) = profile("3,10,1001,10,-1,10,1005,10,2,99,0", &[4]);
+
+    assert_eq!(profiler.hot_ranges(&program), vec![(0, 10, 10)]);
+    let report = profiler.report(&program, 1);
+    assert!(report.contains("add [10], -1, [10]"), "{}", report);
+    assert!(report.contains("jnz [10], 2"), "{}", report);
+}
+
+#[test]
+fn blocked_time_is_only_counted_while_waiting() {
+    let mut machine = Machine::new(loader::parse("3,0,99").unwrap());
+    let mut profiler = Profiler::new();
+
+    assert_eq!(machine.run_traced(&Limits::none(), &mut profiler), Ok(Status::NeedsInput));
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    machine.push_input(1);
+    assert_eq!(machine.run_traced(&Limits::none(), &mut profiler), Ok(Status::Halted));
+
+    assert!(profiler.blocked() >= std::time::Duration::from_millis(20));
+    assert_eq!(profiler.total(), 2);
+}
+
+#[test]
+fn blocked_time_includes_waiting_on_a_channel() {
+    let (input, inputs) = mpsc::channel();
+    let (outputs, _output) = mpsc::channel();
+    let mut machine = Machine::new(loader::parse("3,0,99").unwrap())
+        .empty_input(EmptyInput::Block)
+        .connect(inputs, outputs);
+    let mut profiler = Profiler::new();
+
+    let sender = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        input.send(1).unwrap();
+    });
+    assert_eq!(machine.run_traced(&Limits::none(), &mut profiler), Ok(Status::Halted));
+    sender.join().unwrap();
+
+    assert!(profiler.blocked() >= Duration::from_millis(20));
+    assert!(profiler.report(&[3, 0, 99], 1).contains("over 1 waits"));
+    assert_eq!(profiler.total(), 2);
+}
+
+#[test]
+fn folded_stacks_follow_the_relative_base() {
+    // Enters a frame at 0, a nested one at 2, then leaves both at once. The
+    // instruction that leaves is still counted inside the frames.
+    let (profiler, _) = profile("109,5,109,3,104,1,109,-8,99", &[]);
+
+    assert_eq!(profiler.folded(), "main 2\nmain;fn@0 1\nmain;fn@0;fn@2 2\n");
+}
+
+#[test]
+fn disassembles_every_mode() {
+    let program = loader::parse("21101,3,-4,5,204,-1,99,7").unwrap();
+
+    assert_eq!(disasm::disassemble(&program, 0, program.len()), vec![
+        (0, String::from("add 3, -4, [rb+5]")),
+        (4, String::from("out [rb-1]")),
+        (6, String::from("halt")),
+        (7, String::from("data 7")),
+    ]);
+}
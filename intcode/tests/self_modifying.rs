@@ -0,0 +1,38 @@
+use intcode::Num;
+use intcode::loader;
+use intcode::machine::{CodeWrite, Machine, Status};
+
+// Prints 7, then turns the print at 0 into a position mode one and loops
+// once more, printing the word at 7 instead
+const REWRITES_OUTPUT : &str = "104,7, 1101,0,4,0, 1001,20,1,20, 1008,20,2,21, 1006,21,0, 99, 0,0, 0,0";
+
+fn run(machine : &mut Machine) -> Vec<Num> {
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    machine.take_outputs()
+}
+
+#[test]
+fn tracks_executed_words() {
+    let mut machine = Machine::new(loader::parse("1101,2,3,7, 104,0, 99, 0").unwrap());
+    run(&mut machine);
+
+    assert!((0..7).all(|address| machine.is_code(address)));
+    assert!(!machine.is_code(7));
+    assert!(machine.code_writes().is_empty());
+}
+
+#[test]
+fn reports_writes_into_executed_code() {
+    let mut machine = Machine::new(loader::parse(REWRITES_OUTPUT).unwrap());
+
+    assert_eq!(run(&mut machine), vec![7, 20]);
+    assert_eq!(machine.code_writes(), vec![CodeWrite { pc : 2, address : 0, count : 2 }]);
+}
+
+#[test]
+fn decode_cache_sees_rewritten_instructions() {
+    let mut machine = Machine::new(loader::parse(REWRITES_OUTPUT).unwrap()).decode_cache(true);
+
+    assert_eq!(run(&mut machine), vec![7, 20]);
+    assert_eq!(machine.code_writes(), vec![CodeWrite { pc : 2, address : 0, count : 2 }]);
+}
@@ -0,0 +1,58 @@
+// The threaded engine should stop, fail and resume exactly where the machine
+// does.
+
+use intcode::Num;
+use intcode::loader;
+use intcode::machine::{Error, Limits, Machine, Status};
+use intcode::threaded::Threaded;
+
+const BOOST : &str = include_str!("../../day9/input.txt");
+
+fn threaded(program : &str) -> Threaded {
+    Threaded::new(loader::parse(program).unwrap())
+}
+
+#[test]
+fn runs_boost_like_the_machine() {
+    for &input in &[1, 2] {
+        let mut machine = Machine::new(loader::parse(BOOST).unwrap());
+        let mut engine = threaded(BOOST);
+        machine.push_input(input);
+        engine.push_input(input);
+
+        assert_eq!(engine.run(), machine.run());
+        assert_eq!(engine.outputs(), machine.outputs());
+        assert_eq!(engine.executed(), machine.executed());
+    }
+}
+
+#[test]
+fn waits_for_input_and_resumes() {
+    let mut engine = threaded("3,9,1001,9,1,9,4,9,99,0");
+
+    assert_eq!(engine.run(), Ok(Status::NeedsInput));
+    assert_eq!(engine.pc(), 0);
+    engine.push_input(41);
+    assert_eq!(engine.run(), Ok(Status::Halted));
+    assert_eq!(engine.take_outputs(), vec![42 as Num]);
+    assert_eq!(engine.executed(), 4);
+}
+
+#[test]
+fn budget_stops_before_the_next_instruction() {
+    let mut engine = threaded("1105,1,0");
+    let limits = Limits::none().instructions(100);
+
+    assert_eq!(engine.run_with(&limits), Ok(Status::BudgetExceeded));
+    assert_eq!(engine.executed(), 100);
+    assert_eq!(engine.step(), Ok(None));
+    assert_eq!(engine.executed(), 101);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(threaded("1,0,0,0,42").run(), Err(Error::UnknownOpcode { pc : 4, opcode : 42 }));
+    assert_eq!(threaded("4,-1,99").run(), Err(Error::NegativeAddress { pc : 0, address : -1 }));
+    assert_eq!(threaded("304,0,99").run(), Err(Error::InvalidMode { pc : 0, mode : 3 }));
+    assert_eq!(threaded("11101,1,1,0,99").run(), Err(Error::InvalidMode { pc : 0, mode : 1 }));
+}
@@ -0,0 +1,70 @@
+// These check what gets compiled and what is left to the interpreter. The
+// generated code itself is compiled and run against the machine by the tests
+// in day9/src/compiled.rs, whose build.rs compiles the fixtures.
+
+use std::collections::VecDeque;
+
+use intcode::loader;
+use intcode::machine::{Machine, State, Status};
+use intcode::transpile::transpile;
+
+fn transpiled(program : &str) -> String {
+    transpile(&loader::parse(program).unwrap())
+}
+
+#[test]
+fn blocks_become_match_arms() {
+    // Counts [11] down from 3, printing it each time
+    let text = transpiled("4,11, 1001,11,-1,11, 1005,11,0, 99, 0,3");
+
+    assert!(text.contains("0 => {"), "{}", text);
+    assert!(text.contains("9 => {"), "{}", text);
+    assert!(text.contains("self.state.pc = if condition != 0 { 0 } else { 9 };"), "{}", text);
+    assert!(text.contains("return Ok(Status::Halted);"), "{}", text);
+    assert!(text.contains("impl Computer for Program"), "{}", text);
+    assert!(text.contains("matches!(address, 0..=9)"), "{}", text);
+}
+
+#[test]
+fn written_operands_are_read_at_run_time() {
+    // The add stores the jump target at 6 before the jump reads it
+    let text = transpiled("1101,0,9,6, 1005,12,0, 4,13, 99, 0,0,1,7");
+
+    assert!(text.contains("let target : Num = self.state.memory[6];"), "{}", text);
+    assert!(text.contains("address(target, 4)?"), "{}", text);
+    // The word is left out of the compiled code
+    assert!(text.contains("matches!(address, 0..=5 | 7..=9)"), "{}", text);
+}
+
+#[test]
+fn rewritten_instructions_are_interpreted() {
+    // The add at 0 turns the word at 4 into an output. The analysis can't
+    // see past it, so the halt is interpreted as well.
+    let text = transpiled("1101,0,104,4, 0,42, 99");
+
+    assert!(text.contains("0 => {"), "{}", text);
+    assert!(text.contains("self.state.pc = 4;"), "{}", text);
+    assert!(!text.contains("4 => {"), "{}", text);
+    assert!(text.contains("matches!(address, 0..=3)"), "{}", text);
+}
+
+#[test]
+fn machine_state_hands_over() {
+    let mut machine = Machine::new(loader::parse("3,9,1001,9,1,9,4,9,99,0").unwrap());
+    assert_eq!(machine.run(), Ok(Status::NeedsInput));
+
+    let mut state = machine.into_state();
+    assert_eq!(state.pc, 0);
+    state.inputs.push_back(41);
+
+    let mut machine = Machine::from_state(state);
+    assert_eq!(machine.run(), Ok(Status::Halted));
+    assert_eq!(machine.into_state(), State {
+        memory : loader::parse("3,9,1001,9,1,9,4,9,99,42").unwrap(),
+        pc : 8,
+        relative_base : 0,
+        inputs : VecDeque::new(),
+        outputs : vec![42],
+        halted : true,
+    });
+}